@@ -1,4 +1,4 @@
-use crate::sql::{parse_sql, ParsedSql};
+use crate::sql::{parse_sql, quoted_ranges, ParsedSql};
 use rusqlite::Connection;
 use std::borrow::Cow;
 use std::collections::HashMap;
@@ -14,6 +14,70 @@ const INITIAL_KEYWORDS: [&str; 15] = [
     "UPDATE", "ALTER", "BEGIN", "END", "COMMIT", "ROLLBACK",
 ];
 
+/// Keywords after which an unqualified identifier is a column name.
+const COLUMN_KEYWORDS: [&str; 20] = [
+    "SELECT",
+    "WHERE",
+    "BY",
+    "SET",
+    "AND",
+    "OR",
+    "ON",
+    "HAVING",
+    "WHEN",
+    "THEN",
+    "ELSE",
+    "NOT",
+    "DISTINCT",
+    "CASE",
+    "RETURNING",
+    "IN",
+    "IS",
+    "LIKE",
+    "GLOB",
+    "BETWEEN",
+];
+
+/// Keywords after which an identifier is something other than a column name.
+const OTHER_KEYWORDS: [&str; 11] = [
+    "FROM", "JOIN", "INTO", "UPDATE", "TABLE", "INDEX", "VIEW", "TRIGGER", "AS", "PRAGMA", "VALUES",
+];
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Replace strings, quoted names and comments with spaces, so that the keywords and semicolons in
+/// them are ignored. Byte offsets stay the same.
+fn blank_quotes(sql: &str) -> String {
+    let mut code = sql.to_string();
+    for range in quoted_ranges(sql) {
+        let spaces = " ".repeat(range.len());
+        code.replace_range(range, &spaces);
+    }
+    code
+}
+
+/// Find out if the word before `end` is in a position where a column name is expected, by
+/// looking at the closest keyword before it.
+fn is_column_position(sql: &str, end: usize) -> bool {
+    let code = blank_quotes(&sql[..end]);
+    let statement_start = code.rfind(';').map_or(0, |index| index + 1);
+    code[statement_start..]
+        .split(|c: char| !is_identifier_char(c))
+        .rev()
+        .find_map(|word| {
+            if COLUMN_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(word)) {
+                Some(true)
+            } else if OTHER_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(word)) {
+                Some(false)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(false)
+}
+
 fn starts_with(item: &str, input: &str) -> bool {
     input.len() <= item.len() && item[..input.len()].eq_ignore_ascii_case(input)
 }
@@ -26,6 +90,40 @@ fn match_case<'i>(item: &'i str, input: &str) -> Cow<'i, str> {
     }
 }
 
+/// Collect the names of tables used by a statement in `range`. This includes tables in FROM
+/// clauses and the targets of UPDATE and DELETE statements.
+fn referenced_tables<'a>(
+    node: Node<'_>,
+    sql: &'a str,
+    range: std::ops::Range<usize>,
+) -> Vec<&'a str> {
+    let mut tables = vec![];
+    if node.end_byte() <= range.start || node.start_byte() >= range.end {
+        return tables;
+    }
+    if matches!(node.kind(), "table_or_subquery" | "qualified_table_name") {
+        let mut cursor = node.walk();
+        let identifiers = node
+            .named_children(&mut cursor)
+            .filter(|child| child.kind() == "identifier")
+            .collect::<Vec<_>>();
+        // `schema.table` has two identifiers, followed by an optional alias.
+        let table = match identifiers.as_slice() {
+            [schema, table, ..] if sql[schema.end_byte()..table.start_byte()].contains('.') => {
+                Some(table)
+            }
+            [table, ..] => Some(table),
+            [] => None,
+        };
+        tables.extend(table.map(|table| &sql[table.byte_range()]));
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        tables.extend(referenced_tables(child, sql, range.clone()));
+    }
+    tables
+}
+
 #[derive(Debug)]
 struct QueryNames<'a> {
     ctes: HashMap<&'a str, Vec<String>>,
@@ -42,7 +140,7 @@ impl Completions {
     }
 
     /// Maybe cache this later
-    fn get_table_names(&self) -> Vec<String> {
+    pub fn get_table_names(&self) -> Vec<String> {
         let mut stmt = self
             .connection
             .prepare_cached("SELECT name FROM sqlite_schema WHERE type = 'table' ORDER BY name ASC")
@@ -55,6 +153,112 @@ impl Completions {
         tables.unwrap()
     }
 
+    fn get_column_names(&self, table_name: &str) -> Vec<String> {
+        let mut stmt = self
+            .connection
+            .prepare_cached("SELECT name FROM pragma_table_xinfo(?)")
+            .unwrap();
+        let columns = stmt
+            .query_map([table_name], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<String>, _>>();
+
+        columns.unwrap_or_default()
+    }
+
+    /// Get the columns of the tables that a statement refers to, or of all tables if it does not
+    /// refer to any.
+    pub fn get_referenced_column_names(&self, sql: &str) -> Vec<String> {
+        let Ok(tree) = parse_sql(sql) else {
+            return vec![];
+        };
+        let mut tables = referenced_tables(tree.tree.root_node(), sql, 0..sql.len())
+            .into_iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        if tables.is_empty() {
+            tables = self.get_table_names();
+        }
+        tables
+            .iter()
+            .flat_map(|table| self.get_column_names(table))
+            .collect()
+    }
+
+    /// Get the columns of a table, view, or CTE, that is referred to by `name` in a query.
+    fn get_columns_for(&self, names: &QueryNames<'_>, name: &str) -> Vec<String> {
+        let name = names
+            .table_aliases
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+            .map_or(name, |(_, table)| table);
+        if let Some((_, columns)) = names
+            .ctes
+            .iter()
+            .find(|(cte, _)| cte.eq_ignore_ascii_case(name))
+        {
+            return columns.clone();
+        }
+        self.get_column_names(name)
+    }
+
+    fn get_column_completions(
+        &self,
+        tree: &ParsedSql<'_>,
+        names: &QueryNames<'_>,
+        pos: usize,
+    ) -> Vec<(usize, String)> {
+        let sql = tree.source;
+        let word_start = sql[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_identifier_char(*c))
+            .last()
+            .map_or(pos, |(index, _)| index);
+        let word = &sql[word_start..pos];
+        // Nothing is completed inside strings and comments.
+        if quoted_ranges(&sql[..word_start])
+            .last()
+            .is_some_and(|range| range.end == word_start)
+        {
+            return vec![];
+        }
+
+        let mut columns = if let Some(qualifier_end) = sql[..word_start].strip_suffix('.') {
+            // alias.column or table.column
+            let qualifier_start = qualifier_end
+                .char_indices()
+                .rev()
+                .take_while(|(_, c)| is_identifier_char(*c))
+                .last()
+                .map_or(qualifier_end.len(), |(index, _)| index);
+            let qualifier = &qualifier_end[qualifier_start..];
+            if qualifier.is_empty() {
+                return vec![];
+            }
+            self.get_columns_for(names, qualifier)
+        } else {
+            if word.is_empty() || !is_column_position(sql, word_start) {
+                return vec![];
+            }
+            let code = blank_quotes(sql);
+            let statement_start = code[..word_start].rfind(';').map_or(0, |index| index + 1);
+            let statement_end = code[pos..].find(';').map_or(sql.len(), |index| pos + index);
+            referenced_tables(tree.tree.root_node(), sql, statement_start..statement_end)
+                .into_iter()
+                .flat_map(|table| self.get_columns_for(names, table))
+                .collect()
+        };
+
+        columns.sort();
+        columns.dedup();
+        columns
+            .into_iter()
+            .filter(|column| starts_with(column, word))
+            .map(|column| (word_start, column))
+            .collect()
+    }
+
     fn get_pragma_list(&self) -> Vec<String> {
         let mut stmt = self
             .connection
@@ -116,6 +320,22 @@ impl Completions {
     pub fn get_completions(&self, sql: &str, pos: usize) -> Vec<(usize, String)> {
         let tree = parse_sql(sql).unwrap();
         let names = self.parse_names(&tree);
+        let completions = self.get_node_completions(&tree, &names, pos);
+        if !completions.is_empty() {
+            return completions;
+        }
+
+        self.get_column_completions(&tree, &names, pos)
+    }
+
+    /// Complete keywords, table names, and pragmas, based on the syntax node at the cursor.
+    fn get_node_completions(
+        &self,
+        tree: &ParsedSql<'_>,
+        names: &QueryNames<'_>,
+        pos: usize,
+    ) -> Vec<(usize, String)> {
+        let sql = tree.source;
         let root = tree.tree.root_node();
         let max_lookbehind = 5.min(pos);
         let relevant_node = (0..max_lookbehind).find_map(|offset| {
@@ -178,3 +398,76 @@ impl Completions {
         Default::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn column_positions() {
+        let at_end = |sql: &str| is_column_position(sql, sql.len());
+        assert!(at_end("SELECT "));
+        assert!(at_end("SELECT id, "));
+        assert!(at_end("SELECT * FROM users WHERE "));
+        assert!(at_end("SELECT * FROM users WHERE id = 1 AND "));
+        assert!(at_end("SELECT * FROM users ORDER BY "));
+        assert!(at_end("update users set "));
+        assert!(!at_end("SELECT * FROM "));
+        assert!(!at_end("SELECT * FROM users JOIN "));
+        assert!(!at_end("SELECT id AS "));
+        assert!(!at_end("INSERT INTO "));
+        assert!(!at_end("SELECT 1; DELETE FROM "));
+        assert!(!at_end(""));
+        // Keywords in strings, quoted names and comments don't count.
+        assert!(at_end("SELECT 'from x', "));
+        assert!(at_end("SELECT \"from\", "));
+        assert!(at_end("SELECT id /* FROM */, "));
+        assert!(at_end("SELECT id, ';' -- ; FROM\n, "));
+    }
+
+    /// Complete columns at the `|` in `sql`, against a database with a few tables.
+    fn column_completions(sql: &str) -> Vec<String> {
+        let pos = sql.find('|').unwrap();
+        let sql = sql.replace('|', "");
+        let conn = Rc::new(Connection::open_in_memory().unwrap());
+        conn.execute_batch(
+            "CREATE TABLE users(id, name, email);
+            CREATE TABLE posts(id, user_id, title);",
+        )
+        .unwrap();
+        let completions = Completions::new(conn);
+        let tree = parse_sql(&sql).unwrap();
+        let names = completions.parse_names(&tree);
+        completions
+            .get_column_completions(&tree, &names, pos)
+            .into_iter()
+            .map(|(start, column)| {
+                assert_eq!(start, sql[..pos].trim_end_matches(is_identifier_char).len());
+                column
+            })
+            .collect()
+    }
+
+    #[test]
+    fn complete_columns() {
+        assert_eq!(
+            column_completions("SELECT u.na| FROM users u JOIN posts p ON p.user_id = u.id"),
+            ["name"]
+        );
+        assert_eq!(
+            column_completions("SELECT p.t| FROM users u JOIN posts AS p"),
+            ["title"]
+        );
+        assert_eq!(column_completions("SELECT users.e| FROM users"), ["email"]);
+        assert_eq!(
+            column_completions("SELECT 'from x', na| FROM users"),
+            ["name"]
+        );
+        assert_eq!(
+            column_completions("SELECT * FROM posts WHERE ti| = ';'"),
+            ["title"]
+        );
+        assert!(column_completions("SELECT * FROM users WHERE name = 'u.na|'").is_empty());
+        assert!(column_completions("SELECT * FROM users -- WHERE na|").is_empty());
+    }
+}
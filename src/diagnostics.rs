@@ -0,0 +1,218 @@
+//! Explain errors in SQL statements by pointing at the part of the statement that caused them.
+
+use crate::completions::Completions;
+use crate::sql::parse_sql;
+use std::fmt;
+use tree_sitter::Node;
+
+/// An error in an SQL statement, with the line of the statement that caused it.
+#[derive(Debug)]
+pub struct SqlError {
+    message: String,
+    line: String,
+    /// Where the carets start, in terminal columns.
+    column: usize,
+    width: usize,
+    hint: Option<String>,
+}
+
+impl fmt::Display for SqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.message)?;
+        writeln!(f, "    {}", self.line)?;
+        write!(
+            f,
+            "    {:column$}{}",
+            "",
+            "^".repeat(self.width),
+            column = self.column
+        )?;
+        if let Some(hint) = &self.hint {
+            write!(f, " {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SqlError {}
+
+/// Explain an error from preparing `sql`. Returns `None` if the error is not about a specific
+/// part of the statement.
+pub fn diagnose(error: &rusqlite::Error, sql: &str, completions: &Completions) -> Option<SqlError> {
+    let (message, offset) = match error {
+        rusqlite::Error::SqlInputError { msg, offset, .. } => {
+            (msg.as_str(), usize::try_from(*offset).ok())
+        }
+        rusqlite::Error::SqliteFailure(_, Some(msg)) => (msg.as_str(), None),
+        _ => return None,
+    };
+
+    let mut hint = None;
+    let mut range = offset.map(|offset| offset..token_end(sql, offset));
+    if let Some((kind, name)) = unknown_name(message) {
+        // Qualified names, like `t.column`, are reported with the qualifier.
+        let name = name.rsplit('.').next().unwrap_or(name);
+        let candidates = match kind {
+            "table" => completions.get_table_names(),
+            _ => completions.get_referenced_column_names(sql),
+        };
+        hint = closest_match(name, &candidates).map(|name| format!("did you mean `{}`?", name));
+        range = range.or_else(|| find_word(sql, name));
+    }
+    if range.is_none() {
+        // SQLite does not know where the error is, but the parse tree might.
+        let tree = parse_sql(sql).ok()?;
+        let node = find_problem(tree.tree.root_node())?;
+        if node.is_missing() {
+            hint = hint.or_else(|| Some(format!("expected `{}`", node.kind())));
+        }
+        range = Some(node.start_byte()..node.end_byte());
+    }
+    let range = range?;
+
+    let line_start = sql[..range.start].rfind('\n').map_or(0, |index| index + 1);
+    let line_end = sql[range.start..]
+        .find('\n')
+        .map_or(sql.len(), |index| range.start + index);
+    // Tabs would make the carets line up differently than the line.
+    let line = sql[line_start..line_end].replace('\t', " ");
+    let column = console::measure_text_width(&line[..range.start - line_start]);
+    let width = console::measure_text_width(&sql[range.start..range.end.min(line_end)]);
+    Some(SqlError {
+        message: message.to_string(),
+        line: line.trim_end().to_string(),
+        column,
+        width: width.max(1),
+        hint,
+    })
+}
+
+/// Find the end of the token that starts at `offset`: a word, a quoted name or string, or a single
+/// character.
+fn token_end(sql: &str, offset: usize) -> usize {
+    let rest = &sql[offset..];
+    let Some(first) = rest.chars().next() else {
+        return offset;
+    };
+    let length = match first {
+        '\'' | '"' | '`' => rest[1..].find(first).map_or(rest.len(), |index| index + 2),
+        '[' => rest.find(']').map_or(rest.len(), |index| index + 1),
+        c if c.is_alphanumeric() || c == '_' => rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(rest.len()),
+        c => c.len_utf8(),
+    };
+    offset + length
+}
+
+/// Get the kind and name of an unknown table or column from an error message, like
+/// `no such table: users`.
+fn unknown_name(message: &str) -> Option<(&str, &str)> {
+    let rest = message.strip_prefix("no such ")?;
+    let (kind, name) = rest.split_once(": ")?;
+    matches!(kind, "table" | "column").then_some((kind, name))
+}
+
+/// Find a name in a statement, as a whole word, ignoring case.
+fn find_word(sql: &str, name: &str) -> Option<std::ops::Range<usize>> {
+    let lowercase_sql = sql.to_lowercase();
+    let name = name.to_lowercase();
+    // Lowercasing can change byte lengths, which would make the offsets point elsewhere.
+    if lowercase_sql.len() != sql.len() || name.is_empty() {
+        return None;
+    }
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    lowercase_sql.match_indices(&name).find_map(|(start, _)| {
+        let end = start + name.len();
+        let before = lowercase_sql[..start].chars().next_back();
+        let after = lowercase_sql[end..].chars().next();
+        (!before.is_some_and(is_word_char) && !after.is_some_and(is_word_char))
+            .then_some(start..end)
+    })
+}
+
+/// Find the first node that is missing, or that could not be parsed.
+fn find_problem(node: Node<'_>) -> Option<Node<'_>> {
+    if node.is_missing() || node.is_error() {
+        return Some(node);
+    }
+    if !node.has_error() {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let children = node.children(&mut cursor).collect::<Vec<_>>();
+    children.into_iter().find_map(find_problem)
+}
+
+/// Get the candidate that is most similar to `name`, if it is similar enough to be a typo.
+fn closest_match<'c>(name: &str, candidates: &'c [String]) -> Option<&'c str> {
+    let name = name.trim_matches(|c| matches!(c, '"' | '`' | '[' | ']'));
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// Count the number of single character insertions, deletions and substitutions needed to turn
+/// one string into the other, ignoring case.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.to_lowercase().chars().collect::<Vec<_>>();
+    let b = b.to_lowercase().chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+    use std::rc::Rc;
+
+    fn diagnose_sql(sql: &str) -> String {
+        let conn = Rc::new(Connection::open_in_memory().unwrap());
+        conn.execute_batch("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)")
+            .unwrap();
+        let completions = Completions::new(Rc::clone(&conn));
+        let error = conn.prepare(sql).unwrap_err();
+        diagnose(&error, sql, &completions).unwrap().to_string()
+    }
+
+    #[test]
+    fn unknown_names() {
+        assert_eq!(
+            diagnose_sql("SELECT * FROM usrs"),
+            "no such table: usrs\n    SELECT * FROM usrs\n                  ^^^^ did you mean `users`?"
+        );
+        assert_eq!(
+            diagnose_sql("SELECT id,\n  nme FROM users"),
+            "no such column: nme\n      nme FROM users\n      ^^^ did you mean `name`?"
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            diagnose_sql("SELECT * FORM users"),
+            "near \"FORM\": syntax error\n    SELECT * FORM users\n             ^^^^"
+        );
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("users", "users"), 0);
+        assert_eq!(edit_distance("usrs", "Users"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,373 @@
+//! Print database content as SQL statements.
+
+use crate::format::{format_sql, FormatOptions};
+use crate::highlight::SqlHighlighter;
+use crate::output::{OutputRows, SqlOutput};
+use crate::sql::{quote_identifier, write_sql_literal};
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use termcolor::WriteColor;
+
+struct DumpWriter<'a> {
+    highlighter: &'a SqlHighlighter,
+    format_options: &'a FormatOptions,
+    output: &'a mut dyn WriteColor,
+}
+
+impl<'a> DumpWriter<'a> {
+    fn println(&mut self, sql: &str) -> anyhow::Result<()> {
+        if self.output.supports_color() {
+            writeln!(self.output, "{}", self.highlighter.highlight(sql)?)?;
+        } else {
+            writeln!(self.output, "{}", sql)?;
+        }
+        Ok(())
+    }
+
+    /// Print a statement from the schema, formatted with the `.format` options. Formatting only
+    /// changes whitespace and the case of keywords, so the dump recreates the same schema.
+    fn println_statement(&mut self, sql: &str) -> anyhow::Result<()> {
+        self.println(&format_sql(&format!("{sql};"), self.format_options))
+    }
+}
+
+/// Get the columns that can be inserted into: generated columns are skipped.
+fn insertable_columns(conn: &Connection, table_name: &str) -> anyhow::Result<Vec<String>> {
+    let mut stmt =
+        conn.prepare_cached("SELECT name FROM pragma_table_xinfo(?) WHERE hidden = 0")?;
+    let columns = stmt
+        .query_map([table_name], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(columns)
+}
+
+fn dump_rows(
+    conn: &Connection,
+    highlighter: &SqlHighlighter,
+    output: &mut dyn WriteColor,
+    table_name: &str,
+) -> anyhow::Result<()> {
+    let columns = insertable_columns(conn, table_name)?;
+    if columns.is_empty() {
+        return Ok(());
+    }
+    let column_list = columns
+        .iter()
+        .map(|column| quote_identifier(column))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut rows_stmt = conn.prepare(&format!(
+        "SELECT {} FROM {}",
+        column_list,
+        quote_identifier(table_name)
+    ))?;
+
+    let mut output_rows =
+        SqlOutput::new(&rows_stmt, highlighter, output).with_table_name(table_name.to_string());
+
+    let mut rows_query = rows_stmt.query([])?;
+    while let Some(row) = rows_query.next()? {
+        output_rows.add_row(row)?;
+    }
+    output_rows.finish()
+}
+
+/// Print the schema and content of the database as SQL statements.
+///
+/// `filter` is an exact table name or a GLOB pattern. Only tables matching the filter, and the
+/// indexes, triggers and views that belong to them, are printed.
+pub fn dump(
+    conn: &Connection,
+    highlighter: &SqlHighlighter,
+    format_options: &FormatOptions,
+    filter: Option<&str>,
+    output: &mut dyn WriteColor,
+) -> anyhow::Result<()> {
+    let pattern = filter.unwrap_or("*");
+
+    // Tables come first, so indexes, triggers and views can refer to them. Internal tables are
+    // skipped: they are created by SQLite, and sqlite_sequence is restored separately below.
+    // The shadow tables of a virtual table, like `notes_data` for `notes`, hold its content, so
+    // they are included whenever the virtual table is.
+    let mut tables_stmt = conn.prepare_cached(
+        "SELECT s.name, s.sql, t.type = 'virtual'
+        FROM sqlite_schema s
+        JOIN pragma_table_list t ON t.schema = 'main' AND t.name = s.name
+        WHERE s.type = 'table' AND s.sql IS NOT NULL AND s.name NOT LIKE 'sqlite\\_%' ESCAPE '\\'
+            AND (s.tbl_name GLOB ?1 OR t.type = 'shadow' AND EXISTS (
+                SELECT 1 FROM pragma_table_list v
+                WHERE v.schema = 'main' AND v.type = 'virtual' AND v.name GLOB ?1
+                    AND substr(s.name, 1, length(v.name) + 1) = v.name || '_'
+            ))
+        ORDER BY s.rowid",
+    )?;
+    let tables = tables_stmt
+        .query_map([pattern], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut others_stmt = conn.prepare_cached(
+        "SELECT sql FROM sqlite_schema
+        WHERE type IN ('index', 'trigger', 'view') AND sql IS NOT NULL AND tbl_name GLOB ?
+        ORDER BY rowid",
+    )?;
+    let others = others_stmt
+        .query_map([pattern], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if tables.is_empty() && others.is_empty() {
+        anyhow::bail!("no results for {}", pattern);
+    }
+
+    let mut writer = DumpWriter {
+        highlighter,
+        format_options,
+        output,
+    };
+    writer.println("PRAGMA foreign_keys=OFF;")?;
+    writer.println("BEGIN TRANSACTION;")?;
+
+    let mut writable_schema = false;
+    for (name, sql, is_virtual) in tables {
+        if is_virtual {
+            // Virtual tables create their shadow tables when they are created, but those are
+            // dumped as normal tables. Inserting into the schema directly creates the virtual
+            // table without touching the shadow tables, like the official SQLite CLI does.
+            if !writable_schema {
+                writer.println("PRAGMA writable_schema=ON;")?;
+                writable_schema = true;
+            }
+            let mut insert = String::from(
+                "INSERT INTO sqlite_schema(type, name, tbl_name, rootpage, sql) VALUES('table', ",
+            );
+            write_sql_literal(ValueRef::Text(name.as_bytes()), &mut insert);
+            insert.push_str(", ");
+            write_sql_literal(ValueRef::Text(name.as_bytes()), &mut insert);
+            insert.push_str(", 0, ");
+            write_sql_literal(ValueRef::Text(sql.as_bytes()), &mut insert);
+            insert.push_str(");");
+            writer.println(&insert)?;
+            continue;
+        }
+
+        writer.println_statement(&sql)?;
+        dump_rows(conn, highlighter, &mut *writer.output, &name)?;
+    }
+
+    for sql in others {
+        writer.println_statement(&sql)?;
+    }
+
+    // Restore the AUTOINCREMENT state. sqlite_sequence is created automatically by tables that
+    // use AUTOINCREMENT, and may already contain rows for them.
+    let has_sequence = conn
+        .prepare_cached("SELECT 1 FROM sqlite_schema WHERE name = 'sqlite_sequence'")?
+        .exists([])?;
+    if has_sequence {
+        let mut sequence_stmt =
+            conn.prepare("SELECT name, seq FROM sqlite_sequence WHERE name GLOB ?")?;
+        let mut sequence_query = sequence_stmt.query([pattern])?;
+        let mut first = true;
+        while let Some(row) = sequence_query.next()? {
+            if first {
+                let mut delete = String::from("DELETE FROM sqlite_sequence WHERE name GLOB ");
+                write_sql_literal(ValueRef::Text(pattern.as_bytes()), &mut delete);
+                delete.push(';');
+                writer.println(&delete)?;
+                first = false;
+            }
+            let mut insert = String::from("INSERT INTO sqlite_sequence(name, seq) VALUES(");
+            write_sql_literal(row.get_ref(0)?, &mut insert);
+            insert.push_str(", ");
+            write_sql_literal(row.get_ref(1)?, &mut insert);
+            insert.push_str(");");
+            writer.println(&insert)?;
+        }
+    }
+
+    writer.println("COMMIT;")?;
+    if writable_schema {
+        // Reload the schema, so the virtual tables can be used right away.
+        writer.println("PRAGMA writable_schema=RESET;")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use termcolor::NoColor;
+
+    fn contents(conn: &Connection, table_name: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT * FROM {}", quote_identifier(table_name)))
+            .unwrap();
+        let column_count = stmt.column_count();
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((0..column_count)
+                    .map(|index| format!("{:?}", row.get_ref_unwrap(index)))
+                    .collect::<Vec<_>>()
+                    .join(" | "))
+            })
+            .unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    fn schema(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT type, name, tbl_name, sql FROM sqlite_schema ORDER BY type, name")
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(format!(
+                    "{} {} {} {:?}",
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?.is_some(),
+                ))
+            })
+            .unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    fn dump_to_string(conn: &Connection, filter: Option<&str>) -> String {
+        let highlighter = SqlHighlighter::new();
+        let mut output = NoColor::new(vec![]);
+        dump(
+            conn,
+            &highlighter,
+            &FormatOptions::default(),
+            filter,
+            &mut output,
+        )
+        .unwrap();
+        String::from_utf8(output.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn dump_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE "odd ""name""" (id INTEGER PRIMARY KEY, "it's" TEXT, r REAL, b BLOB, v);
+            INSERT INTO "odd ""name""" VALUES
+                (1, 'it''s', 1.0, X'00ff', NULL),
+                (2, 'line
+break', 0.1, X'', 1e300),
+                (3, CAST(X'ff00' AS TEXT), -9e999, NULL, 'x'),
+                (4, '', 3.141592653589793, NULL, -0.0);
+            CREATE TABLE generated (a INTEGER, b INTEGER GENERATED ALWAYS AS (a * 2));
+            INSERT INTO generated (a) VALUES (1), (2);
+            CREATE TABLE keywords (status TEXT, type TEXT, key TEXT, name TEXT);
+            INSERT INTO keywords VALUES ('a', 'b', 'c', 'd');
+            "#,
+        )
+        .unwrap();
+
+        let script = dump_to_string(&conn, None);
+
+        let restored = Connection::open_in_memory().unwrap();
+        restored.execute_batch(&script).unwrap();
+
+        for table_name in ["odd \"name\"", "generated", "keywords"] {
+            assert_eq!(contents(&conn, table_name), contents(&restored, table_name));
+        }
+    }
+
+    #[test]
+    fn dump_schema_objects() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+            CREATE INDEX users_name ON users (name);
+            CREATE TABLE log (user_id INTEGER, message TEXT);
+            CREATE VIEW named_users AS SELECT * FROM users WHERE name IS NOT NULL;
+            CREATE TRIGGER users_log AFTER INSERT ON users BEGIN
+                INSERT INTO log VALUES (new.id, 'created');
+            END;
+            CREATE VIRTUAL TABLE notes USING fts5(body);
+            INSERT INTO users (name) VALUES ('a'), ('b'), ('c');
+            DELETE FROM users WHERE id = 3;
+            INSERT INTO notes VALUES ('hello world');
+            ",
+        )
+        .unwrap();
+
+        let script = dump_to_string(&conn, None);
+        let restored = Connection::open_in_memory().unwrap();
+        restored.execute_batch(&script).unwrap();
+
+        assert_eq!(schema(&conn), schema(&restored));
+        for table_name in ["users", "log", "sqlite_sequence"] {
+            assert_eq!(contents(&conn, table_name), contents(&restored, table_name));
+        }
+        let matches: i64 = restored
+            .query_row(
+                "SELECT count(*) FROM notes WHERE notes MATCH 'hello'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(matches, 1);
+        // The next id continues after the deleted row.
+        restored
+            .execute("INSERT INTO users (name) VALUES ('d')", [])
+            .unwrap();
+        assert_eq!(restored.last_insert_rowid(), 4);
+    }
+
+    #[test]
+    fn dump_filter() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE users (id INTEGER PRIMARY KEY);
+            CREATE TABLE user_roles (id INTEGER PRIMARY KEY);
+            CREATE INDEX user_roles_id ON user_roles (id);
+            ",
+        )
+        .unwrap();
+
+        let script = dump_to_string(&conn, Some("users"));
+        assert!(script.contains("users"));
+        assert!(!script.contains("user_roles"));
+
+        let script = dump_to_string(&conn, Some("user_*"));
+        assert!(!script.contains("CREATE TABLE users"));
+        assert!(script.contains("user_roles_id"));
+
+        conn.execute_batch(
+            "
+            CREATE VIRTUAL TABLE notes USING fts5(body);
+            CREATE TABLE notes_archive (body TEXT);
+            INSERT INTO notes VALUES ('hello world');
+            ",
+        )
+        .unwrap();
+
+        // The shadow tables are needed to restore the virtual table, but other tables with the
+        // same prefix are not.
+        let script = dump_to_string(&conn, Some("notes"));
+        assert!(script.contains("notes_data"));
+        assert!(!script.contains("notes_archive"));
+
+        let restored = Connection::open_in_memory().unwrap();
+        restored.execute_batch(&script).unwrap();
+        let matches: i64 = restored
+            .query_row(
+                "SELECT count(*) FROM notes WHERE notes MATCH 'hello'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(matches, 1);
+    }
+}
@@ -0,0 +1,452 @@
+//! Format SQL for printing.
+//!
+//! Formatting works on the tokens of the syntax tree, and only changes the whitespace between
+//! them and the case of keywords, so formatted SQL always means the same as the input. SQL that
+//! does not parse is left as it is.
+
+use crate::sql::parse_sql;
+use tree_sitter::Node;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(u8),
+    Tabs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// The indentation of subqueries, column definitions and trigger bodies.
+    pub indent: Indent,
+    /// Write keywords in uppercase. Otherwise they are kept as they are.
+    pub uppercase: bool,
+    /// Number of line breaks after each statement.
+    pub lines_between_queries: u8,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: Indent::Spaces(2),
+            uppercase: false,
+            lines_between_queries: 1,
+        }
+    }
+}
+
+/// Keywords that start a clause, and go on a new line.
+const CLAUSE_KEYWORDS: [&str; 21] = [
+    "SELECT",
+    "FROM",
+    "WHERE",
+    "GROUP",
+    "HAVING",
+    "WINDOW",
+    "ORDER",
+    "LIMIT",
+    "UNION",
+    "INTERSECT",
+    "EXCEPT",
+    "VALUES",
+    "SET",
+    "RETURNING",
+    "JOIN",
+    "LEFT",
+    "RIGHT",
+    "FULL",
+    "INNER",
+    "CROSS",
+    "NATURAL",
+];
+
+/// Keywords that start a join. Only the first one of `LEFT OUTER JOIN` goes on a new line.
+const JOIN_KEYWORDS: [&str; 8] = [
+    "JOIN", "LEFT", "RIGHT", "FULL", "INNER", "CROSS", "NATURAL", "OUTER",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Keyword,
+    Comment,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    kind: TokenKind,
+    /// Whether there was whitespace before the token. Tokens that were not separated stay
+    /// together unless a line break is needed.
+    spaced: bool,
+    /// The kind of the syntax node that contains the token, like `create_table_stmt`.
+    parent: &'static str,
+}
+
+impl Token<'_> {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Keyword && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    fn is_any_keyword(&self, keywords: &[&str]) -> bool {
+        keywords.iter().any(|keyword| self.is_keyword(keyword))
+    }
+}
+
+/// What a bracket or trigger body contains, which decides how its content is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    /// Function arguments, value lists and the like, which stay on one line.
+    Inline,
+    /// A subquery, with one clause per line.
+    Query,
+    /// The column definitions of CREATE TABLE, with one per line.
+    List,
+    /// The statements between BEGIN and END in CREATE TRIGGER.
+    Trigger,
+}
+
+/// Format SQL with the given options. SQL with syntax errors is returned unchanged, except for
+/// leading and trailing whitespace.
+pub fn format_sql(sql: &str, options: &FormatOptions) -> String {
+    match tokens(sql) {
+        Some(tokens) => layout(&tokens, options),
+        None => sql.trim().to_string(),
+    }
+}
+
+/// Get the tokens of SQL from its syntax tree, or None if the SQL can't be formatted safely.
+fn tokens(sql: &str) -> Option<Vec<Token<'_>>> {
+    let tree = parse_sql(sql).ok()?;
+    let root = tree.tree.root_node();
+    if root.has_error() {
+        return None;
+    }
+    let mut nodes = vec![];
+    collect_leaves(root, &mut nodes);
+
+    let mut tokens = vec![];
+    let mut end = 0;
+    for node in nodes {
+        if node.start_byte() < end {
+            return None;
+        }
+        // Text that is not part of a token can't be moved around safely.
+        let gap = &sql[end..node.start_byte()];
+        if !gap.trim().is_empty() {
+            return None;
+        }
+        let text = &sql[node.byte_range()];
+        if text.is_empty() {
+            continue;
+        }
+        let parent = node.parent().map_or("", |parent| parent.kind());
+        let kind = if text.starts_with("--") || text.starts_with("/*") {
+            TokenKind::Comment
+        } else if is_keyword(node.kind(), text) && !parent.contains("name") {
+            TokenKind::Keyword
+        } else {
+            TokenKind::Other
+        };
+        tokens.push(Token {
+            text,
+            kind,
+            spaced: !gap.is_empty(),
+            parent,
+        });
+        end = node.end_byte();
+    }
+    if !sql[end..].trim().is_empty() {
+        return None;
+    }
+    Some(tokens)
+}
+
+fn collect_leaves<'t>(node: Node<'t>, leaves: &mut Vec<Node<'t>>) {
+    if node.child_count() == 0 {
+        leaves.push(node);
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_leaves(child, leaves);
+    }
+}
+
+/// Keywords are the tokens whose syntax node is named after them, like `SELECT`.
+fn is_keyword(node_kind: &str, text: &str) -> bool {
+    node_kind
+        .bytes()
+        .all(|byte| byte.is_ascii_uppercase() || byte == b'_')
+        && node_kind.eq_ignore_ascii_case(text)
+}
+
+/// Where a token goes relative to the one before it.
+enum Separator {
+    None,
+    Space,
+    Lines(usize),
+}
+
+fn layout(tokens: &[Token<'_>], options: &FormatOptions) -> String {
+    let indent = match options.indent {
+        Indent::Spaces(width) => " ".repeat(width.into()),
+        Indent::Tabs => "\t".to_string(),
+    };
+    let mut formatted = String::new();
+    let mut blocks: Vec<Block> = vec![];
+    // Whether the previous token opened a block that starts on a new line.
+    let mut opened_block = false;
+    // Whether no token of the current statement or subquery has been written yet.
+    let mut statement_start = true;
+    let mut previous: Option<&Token<'_>> = None;
+
+    for (index, token) in tokens.iter().enumerate() {
+        // The token that closes a block lines up with the start of the block.
+        let mut closed_block = false;
+        if token.text == ")" && blocks.last().is_some_and(|block| *block != Block::Trigger) {
+            closed_block = blocks.pop() != Some(Block::Inline);
+        } else if token.is_keyword("END")
+            && token.parent == "create_trigger_stmt"
+            && blocks.last() == Some(&Block::Trigger)
+        {
+            blocks.pop();
+            closed_block = true;
+        }
+        let level = blocks
+            .iter()
+            .filter(|block| **block != Block::Inline)
+            .count();
+        let breaks_clauses = !matches!(blocks.last(), Some(Block::Inline | Block::List));
+
+        let separator = match previous {
+            None => Separator::None,
+            Some(previous)
+                if previous.kind == TokenKind::Comment && previous.text.starts_with("--") =>
+            {
+                Separator::Lines(1)
+            }
+            Some(_) if closed_block || opened_block => Separator::Lines(1),
+            Some(previous) if previous.text == "," && blocks.last() == Some(&Block::List) => {
+                Separator::Lines(1)
+            }
+            Some(previous) if previous.text == ";" && blocks.last() == Some(&Block::Trigger) => {
+                Separator::Lines(1)
+            }
+            Some(previous) if previous.text == ";" && blocks.is_empty() => {
+                match options.lines_between_queries {
+                    0 if token.spaced => Separator::Space,
+                    0 => Separator::None,
+                    lines => Separator::Lines(lines.into()),
+                }
+            }
+            Some(previous)
+                if breaks_clauses
+                    && !statement_start
+                    && token.is_any_keyword(&CLAUSE_KEYWORDS)
+                    && !continues_clause(previous, token) =>
+            {
+                Separator::Lines(1)
+            }
+            Some(_) if token.spaced => Separator::Space,
+            Some(_) => Separator::None,
+        };
+        match separator {
+            Separator::None => (),
+            Separator::Space => formatted.push(' '),
+            Separator::Lines(lines) => {
+                formatted.push_str(&"\n".repeat(lines));
+                formatted.push_str(&indent.repeat(level));
+            }
+        }
+        if token.kind == TokenKind::Keyword && options.uppercase {
+            formatted.push_str(&token.text.to_ascii_uppercase());
+        } else {
+            formatted.push_str(token.text);
+        }
+
+        opened_block = false;
+        if token.text == "(" {
+            let next = tokens[index + 1..]
+                .iter()
+                .find(|next| next.kind != TokenKind::Comment);
+            let block = if next.is_some_and(|next| next.is_any_keyword(&["SELECT", "WITH"])) {
+                Block::Query
+            } else if token.parent == "create_table_stmt" {
+                Block::List
+            } else {
+                Block::Inline
+            };
+            blocks.push(block);
+            opened_block = block != Block::Inline;
+        } else if token.is_keyword("BEGIN") && token.parent == "create_trigger_stmt" {
+            blocks.push(Block::Trigger);
+            opened_block = true;
+        }
+        if opened_block || token.text == ";" {
+            statement_start = true;
+        } else if token.kind != TokenKind::Comment {
+            statement_start = false;
+        }
+        previous = Some(token);
+    }
+    formatted
+}
+
+/// Check if a clause keyword continues the clause before it, like JOIN in `LEFT JOIN` or FROM in
+/// `DELETE FROM`.
+fn continues_clause(previous: &Token<'_>, token: &Token<'_>) -> bool {
+    (token.is_any_keyword(&JOIN_KEYWORDS) && previous.is_any_keyword(&JOIN_KEYWORDS))
+        || (token.is_keyword("FROM") && previous.is_any_keyword(&["DELETE", "DISTINCT"]))
+        || (token.is_keyword("VALUES") && previous.is_keyword("DEFAULT"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::quoted_ranges;
+
+    const TEST_KEYWORDS: [&str; 24] = [
+        "SELECT", "FROM", "WHERE", "AND", "GROUP", "BY", "ORDER", "LIMIT", "UNION", "ALL", "LEFT",
+        "JOIN", "ON", "IN", "AS", "WITH", "INSERT", "INTO", "VALUES", "CONFLICT", "DO", "UPDATE",
+        "SET", "OVER",
+    ];
+
+    /// Split SQL into tokens without a syntax tree, so the layout can be tested on its own.
+    fn tokenize(sql: &str) -> Vec<Token<'_>> {
+        let quoted = quoted_ranges(sql);
+        let mut tokens = vec![];
+        let mut index = 0;
+        while index < sql.len() {
+            let rest = &sql[index..];
+            let spaced = rest.starts_with(char::is_whitespace);
+            let start = index + (rest.len() - rest.trim_start().len());
+            if start == sql.len() {
+                break;
+            }
+            let end = if let Some(range) = quoted.iter().find(|range| range.start == start) {
+                range.end
+            } else if sql[start..].starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+                sql[start..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .map_or(sql.len(), |length| start + length)
+            } else {
+                start + 1
+            };
+            let text = &sql[start..end];
+            let kind = if text.starts_with("--") || text.starts_with("/*") {
+                TokenKind::Comment
+            } else if TEST_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(text)) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Other
+            };
+            tokens.push(Token {
+                text,
+                kind,
+                spaced: spaced || tokens.is_empty(),
+                parent: "",
+            });
+            index = end;
+        }
+        tokens
+    }
+
+    fn format(sql: &str, options: &FormatOptions) -> String {
+        layout(&tokenize(sql), options)
+    }
+
+    #[test]
+    fn clauses_on_new_lines() {
+        let options = FormatOptions::default();
+        assert_eq!(
+            format(
+                "select a,   count(*) from t  left join u on t.id = u.id where a in (1, 2)\n\
+                 group by a order by 2 limit 5;",
+                &options
+            ),
+            "select a, count(*)\nfrom t\nleft join u on t.id = u.id\nwhere a in (1, 2)\n\
+             group by a\norder by 2\nlimit 5;"
+        );
+        // Window definitions and other brackets stay on one line.
+        assert_eq!(
+            format("SELECT sum(x) OVER (ORDER BY y) FROM t", &options),
+            "SELECT sum(x) OVER (ORDER BY y)\nFROM t"
+        );
+        assert_eq!(
+            format(
+                "INSERT INTO t VALUES (1) ON CONFLICT (id) DO UPDATE SET x = 'a -- b';",
+                &options
+            ),
+            "INSERT INTO t\nVALUES (1) ON CONFLICT (id) DO UPDATE\nSET x = 'a -- b';"
+        );
+    }
+
+    #[test]
+    fn subqueries_are_indented() {
+        let options = FormatOptions {
+            indent: Indent::Spaces(4),
+            ..Default::default()
+        };
+        assert_eq!(
+            format(
+                "SELECT * FROM t WHERE id IN (SELECT id FROM u) ORDER BY 1",
+                &options
+            ),
+            "SELECT *\nFROM t\nWHERE id IN (\n    SELECT id\n    FROM u\n)\nORDER BY 1"
+        );
+        let options = FormatOptions {
+            indent: Indent::Tabs,
+            ..Default::default()
+        };
+        assert_eq!(
+            format("WITH x AS (SELECT 1) SELECT * FROM x", &options),
+            "WITH x AS (\n\tSELECT 1\n)\nSELECT *\nFROM x"
+        );
+    }
+
+    #[test]
+    fn keywords_and_comments() {
+        let options = FormatOptions {
+            uppercase: true,
+            lines_between_queries: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            format(
+                "select a -- the a\nfrom t; select \"from\" /* x */ from u;",
+                &options
+            ),
+            "SELECT a -- the a\nFROM t;\n\nSELECT \"from\" /* x */\nFROM u;"
+        );
+        // Keywords keep their case without the option, and identifiers always do.
+        assert_eq!(
+            format("select Select_Count from t", &FormatOptions::default()),
+            "select Select_Count\nfrom t"
+        );
+    }
+
+    #[test]
+    fn format_with_syntax_tree() {
+        let options = FormatOptions {
+            uppercase: true,
+            ..Default::default()
+        };
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        for sql in [
+            "create table t (id integer primary key, \"key\" text, type text check (type != ''));",
+            "insert into t (id, type) values (1, 'a') on conflict (id) do update set type = excluded.type;",
+            "select id, row_number() over (partition by type order by id) as n from t where id in (select id from t) -- done\n;",
+            "create trigger tr after insert on t begin update t set type = 'b'; delete from t where id < 0; end;",
+        ] {
+            let formatted = format_sql(sql, &options);
+            // Only whitespace and the case of keywords changed.
+            let words = |sql: &str| {
+                sql.split_whitespace()
+                    .collect::<String>()
+                    .to_ascii_lowercase()
+            };
+            assert_eq!(words(&formatted), words(sql));
+            conn.execute_batch(&formatted).unwrap();
+        }
+        // SQL with errors is not changed.
+        assert_eq!(format_sql(" select from ", &options), "select from");
+    }
+}
@@ -1,8 +1,10 @@
 //! Format SQL strings with highlighting.
 
+use crate::theme::Theme;
 use std::cell::RefCell;
 use std::io::Write;
-use termcolor::{Buffer, Color, ColorSpec, WriteColor};
+use std::ops::Range;
+use termcolor::{Buffer, ColorSpec, WriteColor};
 use tree_sitter_highlight::{
     Error as HighlightError, Highlight, HighlightConfiguration, HighlightEvent, Highlighter,
 };
@@ -21,6 +23,7 @@ const QUERY_NAMES: [&str; 8] = [
 pub struct SqlHighlighter {
     highlighter: RefCell<Highlighter>,
     sql_config: HighlightConfiguration,
+    theme: Theme,
 }
 
 impl SqlHighlighter {
@@ -38,13 +41,31 @@ impl SqlHighlighter {
         Self {
             highlighter: RefCell::new(highlighter),
             sql_config,
+            theme: Theme::default(),
         }
     }
 
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
     pub fn highlight(&self, sql: &str) -> anyhow::Result<String> {
+        self.highlight_with_overlays(sql, &[])
+    }
+
+    /// Highlight SQL, and apply extra styles to parts of it, like syntax errors.
+    pub fn highlight_with_overlays(
+        &self,
+        sql: &str,
+        overlays: &[Overlay],
+    ) -> anyhow::Result<String> {
         let mut highlighter = self.highlighter.borrow_mut();
         let highlights = highlighter.highlight(&self.sql_config, sql.as_bytes(), None, |_| None)?;
-        to_ansi(sql.as_bytes(), highlights)
+        to_ansi(sql.as_bytes(), highlights, &self.theme, overlays)
     }
 }
 
@@ -54,37 +75,79 @@ impl Default for SqlHighlighter {
     }
 }
 
-/// Turn highlights into ANSI sequences. Accepts highlights in any language, but the name order
-/// needs to match.
+/// A style that replaces the highlighting of a byte range.
+pub struct Overlay {
+    pub range: Range<usize>,
+    pub spec: ColorSpec,
+}
+
+/// Turn highlights into ANSI sequences, with overlays on top. Accepts highlights in any language,
+/// but the name order needs to match.
 pub fn to_ansi(
     source: &[u8],
     highlights: impl Iterator<Item = Result<HighlightEvent, HighlightError>>,
+    theme: &Theme,
+    overlays: &[Overlay],
 ) -> anyhow::Result<String> {
     let mut buf = Buffer::ansi();
 
-    let mut keyword = ColorSpec::new();
-    keyword.set_fg(Some(Color::Blue)).set_bold(true);
-    let mut number = ColorSpec::new();
-    number.set_fg(Some(Color::Yellow)).set_bold(true);
-    let mut string = ColorSpec::new();
-    string.set_fg(Some(Color::Magenta)).set_bold(true);
-    let mut comment = ColorSpec::new();
-    comment.set_fg(Some(Color::Green)).set_bold(true);
-    let mut parameter = ColorSpec::new();
-    parameter.set_fg(Some(Color::Magenta)).set_bold(true);
-
+    // Highlights can be nested, and the outer style is restored after an overlay.
+    let mut styles: Vec<Option<&ColorSpec>> = vec![];
     for event in highlights {
         match event? {
-            HighlightEvent::HighlightStart(Highlight(style)) => match style {
-                0 => buf.set_color(&keyword)?,
-                1 => buf.set_color(&number)?,
-                2 => buf.set_color(&string)?,
-                4 => buf.set_color(&comment)?,
-                7 => buf.set_color(&parameter)?,
-                _ => (),
-            },
-            HighlightEvent::Source { start, end } => buf.write_all(&source[start..end])?,
-            HighlightEvent::HighlightEnd => buf.reset()?,
+            HighlightEvent::HighlightStart(Highlight(style)) => {
+                // In the order of `QUERY_NAMES`.
+                let spec = [
+                    &theme.keyword,
+                    &theme.number,
+                    &theme.string,
+                    &theme.constant,
+                    &theme.comment,
+                    &theme.operator,
+                    &theme.punctuation,
+                    &theme.parameter,
+                ]
+                .get(style)
+                .copied();
+                if let Some(spec) = spec {
+                    buf.set_color(spec)?;
+                }
+                styles.push(spec);
+            }
+            HighlightEvent::Source { start, end } => {
+                let mut pos = start;
+                while pos < end {
+                    let overlay = overlays.iter().find(|overlay| overlay.range.contains(&pos));
+                    let next = match overlay {
+                        Some(overlay) => overlay.range.end.min(end),
+                        None => overlays
+                            .iter()
+                            .map(|overlay| overlay.range.start)
+                            .filter(|&start| start > pos)
+                            .min()
+                            .map_or(end, |start| start.min(end)),
+                    };
+                    match overlay {
+                        Some(overlay) => {
+                            buf.set_color(&overlay.spec)?;
+                            buf.write_all(&source[pos..next])?;
+                            buf.reset()?;
+                            if let Some(Some(spec)) = styles.last() {
+                                buf.set_color(spec)?;
+                            }
+                        }
+                        None => buf.write_all(&source[pos..next])?,
+                    }
+                    pos = next;
+                }
+            }
+            HighlightEvent::HighlightEnd => {
+                styles.pop();
+                buf.reset()?;
+                if let Some(Some(spec)) = styles.last() {
+                    buf.set_color(spec)?;
+                }
+            }
         }
     }
 
@@ -0,0 +1,327 @@
+//! Import CSV and TSV files into tables.
+
+use crate::sql::quote_identifier;
+use indicatif::ProgressBar;
+use rusqlite::Connection;
+use std::path::Path;
+
+/// Number of rows used to guess column types when creating a new table.
+const SAMPLE_SIZE: usize = 1000;
+
+/// Ordered from narrowest to widest, so `max` picks the type that fits both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ColumnType {
+    Integer,
+    Real,
+    Text,
+}
+
+impl ColumnType {
+    fn as_sql(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+        }
+    }
+
+    /// Find the narrowest type that can store all of the values. Empty values are imported as
+    /// NULL in number columns, so they fit any type.
+    fn infer<'v>(values: impl Iterator<Item = &'v [u8]>) -> Self {
+        let mut column_type = None;
+        for value in values.filter(|value| !value.is_empty()) {
+            let value = std::str::from_utf8(value).unwrap_or_default();
+            let value_type = if loses_formatting(value.trim()) {
+                return ColumnType::Text;
+            } else if value.trim().parse::<i64>().is_ok() {
+                ColumnType::Integer
+            } else if is_decimal_literal(value.trim()) {
+                ColumnType::Real
+            } else {
+                return ColumnType::Text;
+            };
+            column_type = column_type.max(Some(value_type));
+        }
+        column_type.unwrap_or(ColumnType::Text)
+    }
+}
+
+/// Check if a value is a finite decimal number, like `2.5` or `-1e3`. Rust also parses `NaN` and
+/// `inf` as floats, but those are text in a CSV file.
+fn is_decimal_literal(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|byte| byte.is_ascii_digit() || matches!(byte, b'+' | b'-' | b'.' | b'e' | b'E'))
+        && value.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+/// Check if a value would change when stored as a number, like the leading zeros of `007` or the
+/// sign of `+5`. Zip codes, phone numbers and IDs are often written like that.
+fn loses_formatting(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    value.starts_with('+')
+        || digits.starts_with('0') && digits[1..].starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Check if a declared column type has TEXT affinity, using SQLite's rules.
+fn has_text_affinity(declared_type: &str) -> bool {
+    let declared_type = declared_type.to_ascii_uppercase();
+    !declared_type.contains("INT")
+        && ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|name| declared_type.contains(name))
+}
+
+/// Parse a field delimiter. Accepts a single ASCII character, or `\t` or `tab` for tabs.
+pub fn parse_delimiter(input: &str) -> Result<u8, String> {
+    match input {
+        "\\t" | "tab" => Ok(b'\t'),
+        _ if input.len() == 1 && input.is_ascii() => Ok(input.as_bytes()[0]),
+        _ => Err("delimiter must be a single ASCII character".to_string()),
+    }
+}
+
+pub struct ImportOptions {
+    /// Field delimiter. If not given, it is guessed from the file extension.
+    pub delimiter: Option<u8>,
+    /// Whether the first row contains column names.
+    pub header: bool,
+    /// Number of rows to skip at the start of the file.
+    pub skip: usize,
+}
+
+/// Get the declared type of each column of a table.
+fn table_column_types(conn: &Connection, table_name: &str) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare_cached("SELECT type FROM pragma_table_info(?)")?;
+    let columns = stmt
+        .query_map([table_name], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(columns)
+}
+
+/// Import a CSV or TSV file into a table, and return the number of imported rows.
+///
+/// If the table does not exist, it is created, using the column types that fit the first rows
+/// of the file. Empty fields are imported as empty strings in TEXT columns, and as NULL in other
+/// columns. Missing fields are always NULL.
+pub fn import(
+    conn: &Connection,
+    filename: &Path,
+    table_name: &str,
+    options: &ImportOptions,
+) -> anyhow::Result<usize> {
+    let delimiter = options.delimiter.unwrap_or_else(|| {
+        match filename.extension().and_then(|ext| ext.to_str()) {
+            Some("tsv" | "tab") => b'\t',
+            _ => b',',
+        }
+    });
+    let file = std::fs::File::open(filename)?;
+    let bar = ProgressBar::new(file.metadata()?.len());
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bar.wrap_read(file));
+    let records = reader.byte_records().skip(options.skip);
+
+    // A savepoint works both inside and outside of a transaction.
+    conn.execute_batch("SAVEPOINT sqc_import")?;
+    let result = import_records(conn, records, table_name, options.header);
+    if result.is_ok() {
+        conn.execute_batch("RELEASE sqc_import")?;
+    } else {
+        conn.execute_batch("ROLLBACK TO sqc_import; RELEASE sqc_import")?;
+    }
+    bar.finish_and_clear();
+    result
+}
+
+fn import_records(
+    conn: &Connection,
+    records: impl Iterator<Item = csv::Result<csv::ByteRecord>>,
+    table_name: &str,
+    has_header: bool,
+) -> anyhow::Result<usize> {
+    let mut records = records.peekable();
+    let existing_columns = table_column_types(conn, table_name)?;
+    let header = if has_header {
+        records.next().transpose()?
+    } else {
+        None
+    };
+
+    let mut sample = Vec::new();
+    if existing_columns.is_empty() {
+        while sample.len() < SAMPLE_SIZE {
+            match records.next_if(|record| record.is_ok()) {
+                Some(record) => sample.push(record?),
+                None => break,
+            }
+        }
+        let num_columns = header
+            .iter()
+            .chain(&sample)
+            .map(|record| record.len())
+            .max()
+            .unwrap_or(0);
+        if num_columns == 0 {
+            anyhow::bail!("no rows to import");
+        }
+
+        let columns = (0..num_columns)
+            .map(|index| {
+                let name = header
+                    .as_ref()
+                    .and_then(|header| header.get(index))
+                    .map(|name| String::from_utf8_lossy(name).into_owned())
+                    .unwrap_or_else(|| format!("c{}", index + 1));
+                let column_type =
+                    ColumnType::infer(sample.iter().filter_map(|record| record.get(index)));
+                format!("{} {}", quote_identifier(&name), column_type.as_sql())
+            })
+            .collect::<Vec<_>>();
+        conn.execute(
+            &format!(
+                "CREATE TABLE {} ({})",
+                quote_identifier(table_name),
+                columns.join(", ")
+            ),
+            [],
+        )?;
+    }
+
+    let text_columns = table_column_types(conn, table_name)?
+        .iter()
+        .map(|declared_type| has_text_affinity(declared_type))
+        .collect::<Vec<_>>();
+    // The sampled rows still need to be inserted, before the rest.
+    let records = sample.into_iter().map(Ok).chain(records);
+    insert_rows(conn, table_name, &text_columns, records)
+}
+
+fn insert_rows(
+    conn: &Connection,
+    table_name: &str,
+    text_columns: &[bool],
+    records: impl Iterator<Item = csv::Result<csv::ByteRecord>>,
+) -> anyhow::Result<usize> {
+    let num_columns = text_columns.len();
+    let placeholders = vec!["?"; num_columns].join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "INSERT INTO {} VALUES({})",
+        quote_identifier(table_name),
+        placeholders
+    ))?;
+
+    let mut count = 0;
+    for record in records {
+        let record = record?;
+        if record.len() > num_columns {
+            let line = record.position().map_or(0, |position| position.line());
+            anyhow::bail!(
+                "line {}: expected {} fields, but found {}",
+                line,
+                num_columns,
+                record.len()
+            );
+        }
+        // Missing fields are imported as NULL, and so are empty fields, except in TEXT columns.
+        let values = text_columns
+            .iter()
+            .enumerate()
+            .map(|(index, &is_text)| {
+                record
+                    .get(index)
+                    .filter(|value| is_text || !value.is_empty())
+                    .map(String::from_utf8_lossy)
+            })
+            .collect::<Vec<_>>();
+        stmt.execute(rusqlite::params_from_iter(values.iter()))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infer_column_types() {
+        let infer = |values: &[&str]| ColumnType::infer(values.iter().map(|v| v.as_bytes()));
+        assert_eq!(infer(&["1", "-2", ""]), ColumnType::Integer);
+        assert_eq!(infer(&["1", "2.5"]), ColumnType::Real);
+        assert_eq!(infer(&["1", "2.5", "x"]), ColumnType::Text);
+        assert_eq!(infer(&["", ""]), ColumnType::Text);
+        assert_eq!(infer(&["1e3", "-.5"]), ColumnType::Real);
+        assert_eq!(infer(&["0", "-0.5", "0e1"]), ColumnType::Real);
+        // Leading zeros and plus signs would be lost in a number column.
+        assert_eq!(infer(&["1", "007"]), ColumnType::Text);
+        for value in [
+            "NaN",
+            "inf",
+            "-infinity",
+            "1e999",
+            "007",
+            "-01234",
+            "00.5",
+            "+5",
+            "+1.5",
+        ] {
+            assert_eq!(infer(&["1.5", value]), ColumnType::Text);
+        }
+    }
+
+    #[test]
+    fn import_csv() {
+        let path = std::env::temp_dir().join(format!("sqc-import-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "generated by a tool\nid,name,score\n1,alice,2.5\n2,\"bob, jr\",\n3,carol,3\n4,,1\n",
+        )
+        .unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        let options = ImportOptions {
+            delimiter: None,
+            header: true,
+            skip: 1,
+        };
+        let count = import(&conn, &path, "scores", &options).unwrap();
+        assert_eq!(count, 4);
+        // Importing into an existing table appends.
+        let options = ImportOptions {
+            delimiter: Some(b','),
+            header: false,
+            skip: 2,
+        };
+        let count = import(&conn, &path, "scores", &options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(count, 4);
+
+        let schema: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_schema WHERE name = 'scores'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(
+            schema,
+            r#"CREATE TABLE "scores" ("id" INTEGER, "name" TEXT, "score" REAL)"#
+        );
+        let rows = conn
+            .prepare("SELECT id, name, typeof(score) FROM scores")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<Vec<(i64, String, String)>, _>>()
+            .unwrap();
+        assert_eq!(rows.len(), 8);
+        assert_eq!(rows[1], (2, "bob, jr".to_string(), "null".to_string()));
+        assert_eq!(rows[2], (3, "carol".to_string(), "real".to_string()));
+        // Empty text stays an empty string, instead of becoming NULL.
+        assert_eq!(rows[3], (4, "".to_string(), "real".to_string()));
+    }
+}
@@ -1,16 +1,20 @@
 use crate::completions::Completions;
-use crate::highlight::SqlHighlighter;
+use crate::highlight::{Overlay, SqlHighlighter};
+use clap::{CommandFactory as _, ValueHint};
 use rustyline::completion::Completer;
 use rustyline::highlight::Highlighter;
 use rustyline::hint::Hinter;
-use rustyline::validate::Validator;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
 use rustyline::{Context, Helper};
 use std::borrow::Cow;
+use std::cell::Cell;
 
 pub struct EditorHelper {
     name: Option<String>,
     completions: Completions,
     pub highlighter: SqlHighlighter,
+    /// Whether input must be a complete SQL statement before it is accepted.
+    validate_statements: Cell<bool>,
 }
 
 impl EditorHelper {
@@ -19,17 +23,64 @@ impl EditorHelper {
             name,
             completions,
             highlighter: Default::default(),
+            validate_statements: Cell::new(true),
         }
     }
 
     pub fn name(&self) -> Option<&str> {
         self.name.as_deref()
     }
+
+    pub fn completions(&self) -> &Completions {
+        &self.completions
+    }
+
+    /// Turn off statement validation while reading input that is not SQL statements.
+    pub fn set_validate_statements(&self, enabled: bool) {
+        self.validate_statements.set(enabled);
+    }
 }
 
 impl Highlighter for EditorHelper {
-    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
-        match self.highlighter.highlight(line) {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        if line.starts_with('.') {
+            return match self.highlighter.highlight(line) {
+                Ok(highlighted) => highlighted.into(),
+                Err(_) => line.into(),
+            };
+        }
+
+        let mut overlays = vec![];
+        // Errors that reach the end of the input are probably just input that is not finished.
+        let end = line.trim_end().len();
+        let theme = self.highlighter.theme();
+        if let Ok(tree) = crate::sql::parse_sql(line) {
+            overlays.extend(
+                tree.error_ranges()
+                    .into_iter()
+                    .filter(|range| range.end < end)
+                    .map(|range| Overlay {
+                        range,
+                        spec: theme.error.clone(),
+                    }),
+            );
+        }
+
+        // Like most editors, match the bracket under the cursor, or the one right before it.
+        let bracket = [Some(pos), pos.checked_sub(1)]
+            .into_iter()
+            .flatten()
+            .find_map(|pos| Some((pos, crate::sql::matching_bracket(line, pos)?)));
+        if let Some((pos, other)) = bracket {
+            for pos in [pos, other] {
+                overlays.push(Overlay {
+                    range: pos..pos + 1,
+                    spec: theme.bracket.clone(),
+                });
+            }
+        }
+
+        match self.highlighter.highlight_with_overlays(line, &overlays) {
             Ok(highlighted) => highlighted.into(),
             Err(_) => line.into(),
         }
@@ -37,12 +88,10 @@ impl Highlighter for EditorHelper {
 
     fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
         use std::io::Write;
-        use termcolor::{Buffer, Color, ColorSpec, WriteColor};
+        use termcolor::{Buffer, WriteColor};
 
-        let mut grey = ColorSpec::new();
-        grey.set_fg(Some(Color::Ansi256(8))).set_bold(true);
         let mut buf = Buffer::ansi();
-        let _ = buf.set_color(&grey);
+        let _ = buf.set_color(&self.highlighter.theme().hint);
         let _ = buf.write_all(hint.as_bytes());
         let _ = buf.reset();
 
@@ -56,6 +105,132 @@ impl Highlighter for EditorHelper {
     }
 }
 
+/// Complete a file path relative to the working directory.
+fn complete_path(input: &str) -> Vec<String> {
+    let (dir, file_prefix) = match input.rfind(std::path::is_separator) {
+        Some(index) => input.split_at(index + 1),
+        None => ("", input),
+    };
+    let Ok(entries) = std::fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return vec![];
+    };
+
+    let mut paths = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // Only show hidden files if asked for explicitly.
+            if !name.starts_with(file_prefix) || (name.starts_with('.') && file_prefix.is_empty()) {
+                return None;
+            }
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            Some(format!("{dir}{name}{}", if is_dir { "/" } else { "" }))
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+/// Count the positional arguments in a list of words, skipping options and their values like
+/// `split_dot_command` does.
+fn count_positionals(command: &clap::Command, words: &[(String, usize)]) -> usize {
+    let mut words = words.iter();
+    let mut count = 0;
+    while let Some((word, _)) = words.next() {
+        if !word.starts_with("--") {
+            count += 1;
+        } else if crate::option_takes_value(command, word) {
+            words.next();
+        }
+    }
+    count
+}
+
+impl EditorHelper {
+    /// Complete dot command names, and their arguments.
+    fn get_dot_command_completions(
+        &self,
+        line: &str,
+        pos: usize,
+        complete_paths: bool,
+    ) -> Vec<(usize, String)> {
+        let command = crate::DotCommand::command();
+        let dot_command_names = || {
+            command
+                .get_subcommands()
+                .map(|subcommand| subcommand.get_name().to_string())
+        };
+
+        let Some((name, _)) = line[..pos].split_once(' ') else {
+            let input = &line[..pos];
+            return dot_command_names()
+                .filter(|name| name.starts_with(input))
+                .map(|name| (0, format!("{name} ")))
+                .collect();
+        };
+
+        let Some(subcommand) = command.find_subcommand(name) else {
+            return vec![];
+        };
+        // Commands with a single argument get the rest of the line, others are split into words:
+        // see `split_dot_command`.
+        let mut start = name.len() + 1;
+        let mut position = 0;
+        if crate::has_multiple_arguments(subcommand) {
+            let words = crate::split_words(&line[start..pos]);
+            // The last word is the one being completed, unless the cursor is after a space.
+            let (previous_words, input_start) = match words.split_last() {
+                Some((last, previous)) if !line[..pos].ends_with(char::is_whitespace) => {
+                    (previous, start + last.1)
+                }
+                _ => (&words[..], pos),
+            };
+            position = count_positionals(subcommand, previous_words);
+            start = input_start;
+        }
+        let input = &line[start..pos];
+        if subcommand.has_subcommands() {
+            // Only complete the action, like `set` in `.param set`.
+            if position != 0 {
+                return vec![];
+            }
+            return subcommand
+                .get_subcommands()
+                .map(|action| action.get_name().to_string())
+                .filter(|name| name.starts_with(input))
+                .map(|name| (start, format!("{name} ")))
+                .collect();
+        }
+        let Some(arg) = subcommand.get_positionals().nth(position) else {
+            return vec![];
+        };
+
+        let candidates: Vec<String> = if arg.get_value_hint() == ValueHint::FilePath {
+            if !complete_paths {
+                return vec![];
+            }
+            complete_path(input)
+        } else if arg.get_value_names() == Some(&["TABLE".into()]) {
+            self.completions.get_table_names()
+        } else if arg.get_value_names() == Some(&["THEME".into()]) {
+            crate::theme::theme_names()
+        } else if arg.get_value_names() == Some(&["COMMAND".into()]) {
+            dot_command_names().collect()
+        } else {
+            arg.get_possible_values()
+                .into_iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| value.get_name().to_string())
+                .collect()
+        };
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(input))
+            .map(|candidate| (start, candidate))
+            .collect()
+    }
+}
+
 impl Completer for EditorHelper {
     type Candidate = String;
 
@@ -65,7 +240,11 @@ impl Completer for EditorHelper {
         pos: usize,
         _ctx: &Context<'_>,
     ) -> rustyline::Result<(usize, Vec<Self::Candidate>)> {
-        let results = self.completions.get_completions(line, pos);
+        let results = if line.starts_with('.') {
+            self.get_dot_command_completions(line, pos, true)
+        } else {
+            self.completions.get_completions(line, pos)
+        };
         if let Some(first) = results.first() {
             Ok((first.0, results.into_iter().map(|item| item.1).collect()))
         } else {
@@ -78,14 +257,117 @@ impl Hinter for EditorHelper {
     type Hint = String;
 
     fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<Self::Hint> {
-        self.completions
-            .get_completions(line, pos)
+        let results = if line.starts_with('.') {
+            self.get_dot_command_completions(line, pos, false)
+        } else {
+            self.completions.get_completions(line, pos)
+        };
+        results
             .into_iter()
             .next()
             .map(|mut item| item.1.split_off(pos - item.0))
     }
 }
 
-impl Validator for EditorHelper {}
+impl Validator for EditorHelper {
+    fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
+        if self.validate_statements.get() && crate::sql::is_incomplete(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
 
 impl Helper for EditorHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+    use std::rc::Rc;
+
+    fn helper() -> EditorHelper {
+        let conn = Rc::new(Connection::open_in_memory().unwrap());
+        conn.execute_batch("CREATE TABLE users(id); CREATE TABLE user_roles(id);")
+            .unwrap();
+        EditorHelper::new(None, Completions::new(conn))
+    }
+
+    fn complete(helper: &EditorHelper, line: &str) -> Vec<(usize, String)> {
+        helper.get_dot_command_completions(line, line.len(), true)
+    }
+
+    #[test]
+    fn complete_command_names() {
+        let helper = helper();
+        assert_eq!(complete(&helper, ".sch"), vec![(0, ".schema ".to_string())]);
+        assert_eq!(
+            complete(&helper, ".help .sch"),
+            vec![(6, ".schema".to_string())]
+        );
+        assert!(complete(&helper, ".nope ").is_empty());
+    }
+
+    #[test]
+    fn complete_table_names() {
+        let helper = helper();
+        assert_eq!(
+            complete(&helper, ".schema user"),
+            vec![(8, "user_roles".to_string()), (8, "users".to_string())]
+        );
+        // The table is the second argument, after the file name.
+        assert_eq!(
+            complete(&helper, ".import data.csv users"),
+            vec![(17, "users".to_string())]
+        );
+        // Option values are not positional arguments.
+        assert_eq!(
+            complete(&helper, ".import --delimiter ; data.csv users"),
+            vec![(31, "users".to_string())]
+        );
+        assert_eq!(
+            complete(&helper, ".import --header data.csv users"),
+            vec![(26, "users".to_string())]
+        );
+        assert_eq!(
+            complete(&helper, ".import --skip=1 data.csv users"),
+            vec![(26, "users".to_string())]
+        );
+        assert_eq!(
+            complete(&helper, ".import 'my data.csv' us"),
+            vec![(22, "user_roles".to_string()), (22, "users".to_string())]
+        );
+    }
+
+    #[test]
+    fn complete_themes() {
+        let helper = helper();
+        assert_eq!(
+            complete(&helper, ".theme l"),
+            vec![(7, "light".to_string())]
+        );
+    }
+
+    #[test]
+    fn complete_paths() {
+        let helper = helper();
+        let dir = std::env::temp_dir().join(format!("sqc-paths-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        std::fs::write(dir.join("data.csv"), "").unwrap();
+        let dir = dir.to_str().unwrap();
+
+        let line = format!(".read {dir}/da");
+        let completions = complete(&helper, &line);
+        let hint = helper.get_dot_command_completions(&line, line.len(), false);
+        let import = complete(&helper, &format!(".import --delimiter ; {dir}/data."));
+        std::fs::remove_dir_all(dir).unwrap();
+        assert_eq!(
+            completions,
+            vec![(6, format!("{dir}/data.csv")), (6, format!("{dir}/data/"))]
+        );
+        // Hints don't look at the file system.
+        assert!(hint.is_empty());
+        assert_eq!(import, vec![(22, format!("{dir}/data.csv"))]);
+    }
+}
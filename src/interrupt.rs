@@ -0,0 +1,90 @@
+//! Cancel running queries with Ctrl-C.
+
+use rusqlite::{Connection, InterruptHandle};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Set while a pager is running, see `RunningPager`.
+static PAGER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Handles SIGINT: while a query is running, it is interrupted, while a pager is running it is
+/// ignored, and otherwise the process exits like it would without a handler, unless it is
+/// interactive. Line editing is not affected, because the line editor reads Ctrl-C as a key press.
+pub struct QueryInterrupt {
+    handle: Arc<InterruptHandle>,
+    running: Arc<AtomicBool>,
+    /// Set when Ctrl-C is pressed while a query is running, for work that SQLite does not
+    /// interrupt itself, like backups.
+    interrupted: Arc<AtomicBool>,
+}
+
+impl QueryInterrupt {
+    pub fn new(conn: &Connection) -> Self {
+        Self {
+            handle: Arc::new(conn.get_interrupt_handle()),
+            running: Arc::new(AtomicBool::new(false)),
+            interrupted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Install the SIGINT handler. This can only be done once per process. In an interactive
+    /// session, Ctrl-C never exits, so the session goes on and its history is saved at the end.
+    pub fn install(&self, interactive: bool) -> anyhow::Result<()> {
+        let handle = Arc::clone(&self.handle);
+        let running = Arc::clone(&self.running);
+        let interrupted = Arc::clone(&self.interrupted);
+        ctrlc::set_handler(move || {
+            if running.load(Ordering::SeqCst) {
+                interrupted.store(true, Ordering::SeqCst);
+                handle.interrupt();
+            } else if PAGER_RUNNING.load(Ordering::SeqCst) {
+                // The pager gets Ctrl-C too, and handles it.
+            } else if interactive {
+                // Back at the prompt, the line editor handles Ctrl-C.
+            } else {
+                std::process::exit(130);
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Mark a query as running until the returned guard is dropped. Ctrl-C aborts the query
+    /// with an "interrupted" error in the meantime.
+    pub fn start(&self) -> RunningQuery<'_> {
+        self.interrupted.store(false, Ordering::SeqCst);
+        self.running.store(true, Ordering::SeqCst);
+        RunningQuery(self)
+    }
+}
+
+pub struct RunningQuery<'a>(&'a QueryInterrupt);
+
+impl RunningQuery<'_> {
+    /// Check if Ctrl-C was pressed since the query started.
+    pub fn is_interrupted(&self) -> bool {
+        self.0.interrupted.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for RunningQuery<'_> {
+    fn drop(&mut self) {
+        self.0.running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Marks a pager as running until dropped. Ctrl-C is ignored in the meantime, so that it does not
+/// exit sqc while the pager has the terminal.
+pub struct RunningPager(());
+
+impl RunningPager {
+    pub fn start() -> Self {
+        PAGER_RUNNING.store(true, Ordering::SeqCst);
+        Self(())
+    }
+}
+
+impl Drop for RunningPager {
+    fn drop(&mut self) {
+        PAGER_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
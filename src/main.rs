@@ -1,26 +1,41 @@
-use clap::{CommandFactory as _, Parser};
+use anyhow::Context as _;
+use clap::{CommandFactory as _, Parser, ValueHint};
 use directories::ProjectDirs;
+use rusqlite::types::Value;
 use rusqlite::types::ValueRef;
-use rusqlite::{Connection, Statement, ToSql};
+use rusqlite::{Connection, OpenFlags, Statement, StatementStatus};
 use rustyline::error::ReadlineError;
 use rustyline::history::DefaultHistory;
-use rustyline::Editor;
+use rustyline::{ColorMode, Config, Editor};
+use std::collections::{BTreeMap, HashMap};
+use std::io::IsTerminal as _;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 use termcolor::{ColorChoice, StandardStream};
 
 #[macro_use]
 mod macros;
 mod completions;
+mod diagnostics;
+mod dump;
+mod format;
 mod functions;
 mod highlight;
+mod import;
 mod input;
+mod interrupt;
 mod output;
+mod query_plan;
 mod sql;
+mod theme;
 
 use completions::Completions;
+use format::{FormatOptions, Indent};
 use input::EditorHelper;
-use output::{OutputMode, OutputRows, OutputTarget, SqlOutput};
+use interrupt::QueryInterrupt;
+use output::{OutputMode, OutputTarget, PagerMode};
+use sql::ExplainKind;
 
 /// Helper enum to take in "on"/"off" strings and turn them into bool true/false.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
@@ -43,7 +58,10 @@ impl From<OnOff> for bool {
 enum DotCommand {
     /// Print this message or the help of the given subcommand(s).
     #[command(name = ".help")]
-    Help { subcommand: Option<String> },
+    Help {
+        #[arg(value_name = "COMMAND")]
+        subcommand: Option<String>,
+    },
     /// Print names of all tables in the database.
     #[command(name = ".tables")]
     Tables,
@@ -56,35 +74,352 @@ enum DotCommand {
         #[arg(value_enum)]
         output_mode: OutputMode,
     },
+    /// Print column names in csv, markdown and html output.
+    #[command(name = ".headers")]
+    Headers { enabled: OnOff },
+    /// Print the query plan before running each query.
+    #[command(name = ".eqp")]
+    Eqp { enabled: OnOff },
+    /// Print how long each statement takes to run.
+    #[command(name = ".timer")]
+    Timer { enabled: OnOff },
+    /// Print the number of rows and SQLite's performance counters for each statement.
+    #[command(name = ".stats")]
+    Stats { enabled: OnOff },
+    /// Print at most this many rows of each query, and count the rest. 0 prints all rows.
+    #[command(name = ".maxrows")]
+    Maxrows { limit: usize },
+    /// Set when to send output to a pager.
+    #[command(name = ".pager")]
+    Pager {
+        #[arg(value_enum)]
+        mode: PagerMode,
+    },
+    /// Set the color theme: dark, light, or a theme file in the `themes` config directory. Prints
+    /// the available themes without a name.
+    #[command(name = ".theme")]
+    Theme {
+        #[arg(value_name = "THEME")]
+        name: Option<String>,
+    },
     /// Send output to a file, or stdout.
     #[command(name = ".output")]
-    Output { filename: Option<PathBuf> },
+    Output {
+        #[arg(value_hint = ValueHint::FilePath)]
+        filename: Option<PathBuf>,
+    },
     /// Print the schema for a table.
     #[command(name = ".schema")]
-    Schema { table_name: String },
+    Schema {
+        #[arg(value_name = "TABLE")]
+        table_name: String,
+    },
+    /// Format and print SQL, or the last query. The options are kept, and are also used by .echo,
+    /// .schema and .dump.
+    #[command(name = ".format")]
+    Format {
+        /// Indent with this many spaces.
+        #[arg(long, value_name = "N")]
+        indent: Option<u8>,
+        /// Indent with tabs.
+        #[arg(long, conflicts_with = "indent")]
+        tabs: bool,
+        /// Write keywords in uppercase.
+        #[arg(long, value_name = "ON|OFF")]
+        uppercase: Option<OnOff>,
+        /// Number of line breaks after each statement.
+        #[arg(long, value_name = "N")]
+        lines: Option<u8>,
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        sql: Vec<String>,
+    },
     /// Print the parse tree for an SQL statement.
     #[command(name = ".parse")]
     Parse { sql: String },
     /// Print database content as SQL statements.
     #[command(name = ".dump")]
-    Dump { filter: Option<String> },
-    /// Read SQL statements from a file.
+    Dump {
+        /// Only dump tables with this name. May be a GLOB pattern, like `user_*`.
+        #[arg(value_name = "TABLE")]
+        filter: Option<String>,
+    },
+    /// Execute dot commands and SQL statements from a file.
     #[command(name = ".read")]
-    Read { filename: PathBuf },
+    Read {
+        #[arg(value_hint = ValueHint::FilePath)]
+        filename: PathBuf,
+    },
+    /// Import data from a CSV or TSV file into a table. The table is created if it does not exist.
+    #[command(name = ".import")]
+    Import {
+        #[arg(value_hint = ValueHint::FilePath)]
+        filename: PathBuf,
+        #[arg(value_name = "TABLE")]
+        table_name: String,
+        /// Field delimiter, like `;` or `tab`. Defaults to tabs for .tsv files and commas otherwise.
+        #[arg(long, value_parser = import::parse_delimiter)]
+        delimiter: Option<u8>,
+        /// The first row contains column names. This is the default.
+        #[arg(long, overrides_with = "no_header")]
+        header: bool,
+        /// The first row contains data.
+        #[arg(long, overrides_with = "header")]
+        no_header: bool,
+        /// Number of rows to skip at the start of the file.
+        #[arg(long, default_value_t = 0)]
+        skip: usize,
+    },
+    /// Manage named parameters, which are used instead of prompting for values.
+    #[command(name = ".param")]
+    Param {
+        #[command(subcommand)]
+        action: ParamAction,
+    },
     /// Create a full backup of a running database.
     #[command(name = ".backup")]
-    Backup { filename: PathBuf },
+    Backup {
+        #[arg(value_hint = ValueHint::FilePath)]
+        filename: PathBuf,
+    },
+}
+
+/// Check if a dot command's arguments need to be split into words: it has subcommands, or more
+/// than one argument or option, not counting `--help`.
+fn has_multiple_arguments(command: &clap::Command) -> bool {
+    command.has_subcommands()
+        || command
+            .get_arguments()
+            .filter(|arg| arg.get_id() != "help")
+            .count()
+            > 1
+}
+
+/// Split a string into whitespace separated words, and return each word with its start offset.
+/// Words may be quoted with `'` or `"`.
+fn split_words(input: &str) -> Vec<(String, usize)> {
+    let mut words = vec![];
+    let mut chars = input.char_indices();
+    let mut current: Option<(String, usize)> = None;
+    while let Some((index, c)) = chars.next() {
+        let (word, _) = match c {
+            c if c.is_whitespace() => {
+                words.extend(current.take());
+                continue;
+            }
+            _ => current.get_or_insert_with(|| (String::new(), index)),
+        };
+        if c == '\'' || c == '"' {
+            word.extend(chars.by_ref().map(|(_, c)| c).take_while(|&end| end != c));
+        } else {
+            word.push(c);
+        }
+    }
+    words.extend(current);
+    words
+}
+
+/// Check if a word is an option whose value is the next word, like `--skip` in `--skip 2`. The
+/// value can also be part of the option, like `--skip=2`.
+fn option_takes_value(command: &clap::Command, word: &str) -> bool {
+    word.strip_prefix("--").is_some_and(|long| {
+        command
+            .get_arguments()
+            .any(|arg| arg.get_long() == Some(long) && arg.get_action().takes_values())
+    })
+}
+
+/// Get the number of rows changed by INSERT, UPDATE and DELETE statements since the connection was
+/// opened.
+fn total_changes(conn: &Connection) -> anyhow::Result<u64> {
+    Ok(conn.query_row("SELECT total_changes()", [], |row| row.get(0))?)
+}
+
+/// Check if an error comes from writing to a closed pipe, like a pager that was quit.
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == std::io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// Split a dot command into arguments.
+///
+/// Commands that take a single argument get the rest of the line as is, so SQL and file names
+/// with spaces work without quoting. Other commands are split on whitespace, and arguments may be
+/// quoted with `'` or `"`. A `trailing_var_arg` argument gets the rest of the line as is, too.
+fn split_dot_command(request: &str) -> Vec<String> {
+    let request = request.trim();
+    let (name, rest) = request
+        .split_once(char::is_whitespace)
+        .map_or((request, ""), |(name, rest)| (name, rest.trim_start()));
+
+    let root = DotCommand::command();
+    let mut args = vec![name.to_string()];
+    if rest.is_empty() {
+        return args;
+    }
+    let Some(mut command) = root
+        .find_subcommand(name)
+        .filter(|command| has_multiple_arguments(command))
+    else {
+        args.push(rest.to_string());
+        return args;
+    };
+
+    let mut words = split_words(rest).into_iter().peekable();
+    let mut positionals = command.get_positionals();
+    while let Some((word, start)) = words.next() {
+        if let Some(subcommand) = command.find_subcommand(&word) {
+            command = subcommand;
+            positionals = command.get_positionals();
+        } else if word.starts_with("--") {
+            // Keep option values with their option, so they are not counted as positionals.
+            let takes_value = option_takes_value(command, &word);
+            args.push(word);
+            if takes_value {
+                args.extend(words.next().map(|(value, _)| value));
+            }
+            continue;
+        } else if positionals
+            .next()
+            .is_some_and(|arg| arg.is_trailing_var_arg_set())
+        {
+            args.push(rest[start..].to_string());
+            break;
+        }
+        args.push(word);
+    }
+    args
+}
+
+/// Split a script into dot commands and SQL, and return each with the line it starts on.
+///
+/// Dot commands take a single line, like in interactive mode. SQL continues until it is a complete
+/// statement, so statements can span multiple lines.
+fn split_script(script: &str) -> Vec<(usize, String)> {
+    let mut commands = vec![];
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in script.lines().enumerate() {
+        if current.is_none() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.trim_start().starts_with('.') {
+                commands.push((index + 1, line.trim().to_string()));
+                continue;
+            }
+        }
+        let (_, sql) = current.get_or_insert_with(|| (index + 1, String::new()));
+        sql.push_str(line);
+        sql.push('\n');
+        if !crate::sql::is_incomplete(sql) {
+            commands.extend(current.take());
+        }
+    }
+    commands.extend(current);
+    commands
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+enum ParamAction {
+    /// Set a parameter, like `:id`, `@user` or `$x`. Without a prefix, it is used for all of them.
+    Set {
+        name: String,
+        /// An SQL literal, like `NULL`, `42`, `'text'` or `X'00ff'`. Other input is used as text.
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, num_args = 1..)]
+        value: Vec<String>,
+    },
+    /// Remove a parameter.
+    Unset { name: String },
+    /// Print all parameters.
+    List,
+    /// Remove all parameters.
+    Clear,
 }
 
 struct App {
     rl: Editor<EditorHelper, DefaultHistory>,
     conn: Rc<Connection>,
     output_target: OutputTarget,
+    pager: PagerMode,
+    /// The maximum number of rows to print for a query, or 0 to print all rows.
+    max_rows: usize,
+    /// Print the query plan before running queries.
+    eqp: bool,
+    timer: bool,
+    stats: bool,
     output_mode: OutputMode,
+    /// Print column names in the output modes where they are optional.
+    headers: bool,
     echo: bool,
+    interrupt: QueryInterrupt,
+    /// The last value given for each named bind parameter.
+    parameter_values: HashMap<String, String>,
+    /// Values for named bind parameters, set with `.param`.
+    parameters: BTreeMap<String, Value>,
+    /// The scripts that are being executed with `.read`, innermost last.
+    read_stack: Vec<PathBuf>,
+    /// How SQL is formatted by `.format`, `.echo`, `.schema` and `.dump`.
+    format_options: FormatOptions,
+    /// The last SQL input, for `.format` without arguments.
+    last_query: Option<String>,
 }
 
 impl App {
+    fn new(conn: Rc<Connection>, name: Option<String>) -> anyhow::Result<Self> {
+        let interrupt = QueryInterrupt::new(&conn);
+        let completions = Completions::new(Rc::clone(&conn));
+
+        let color_mode = if theme::no_color() {
+            ColorMode::Disabled
+        } else {
+            ColorMode::Enabled
+        };
+        let mut rl = Editor::with_config(Config::builder().color_mode(color_mode).build())?;
+        rl.set_helper(Some(EditorHelper::new(name, completions)));
+
+        Ok(Self {
+            rl,
+            conn,
+            output_target: OutputTarget::Stdout(StandardStream::stdout(ColorChoice::Auto)),
+            pager: PagerMode::Auto,
+            max_rows: 0,
+            eqp: false,
+            timer: false,
+            stats: false,
+            output_mode: OutputMode::Table,
+            headers: true,
+            echo: false,
+            interrupt,
+            parameter_values: HashMap::new(),
+            parameters: BTreeMap::new(),
+            read_stack: vec![],
+            format_options: FormatOptions::default(),
+            last_query: None,
+        })
+    }
+
+    /// Apply the command line flags that were given. This happens after the init file runs, so
+    /// the flags win over it.
+    fn apply_flags(&mut self, opts: &Opts) -> anyhow::Result<()> {
+        if let Some(mode) = opts.mode {
+            self.output_mode = mode;
+        }
+        if let Some(filename) = &opts.output {
+            self.output_target = OutputTarget::File(std::fs::File::create(filename)?);
+        }
+        if opts.echo {
+            self.echo = true;
+        }
+        if opts.header {
+            self.headers = true;
+        } else if opts.no_header {
+            self.headers = false;
+        }
+        Ok(())
+    }
+
     fn run(&mut self) -> anyhow::Result<()> {
         let prompt = format!(
             "{}> ",
@@ -111,43 +446,43 @@ impl App {
     }
 
     fn execute(&mut self, request: &str) -> anyhow::Result<()> {
+        match self.execute_request(request) {
+            // Output stops early when the pager is quit, which is not an error.
+            Err(err) if is_broken_pipe(&err) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn execute_request(&mut self, request: &str) -> anyhow::Result<()> {
         if request.starts_with('.') {
             self.execute_dot_command(request)
         } else {
             if self.echo {
-                let formatted = sqlformat::format(request, &Default::default(), Default::default());
-                let mut output = self.output_target.start();
-                let highlighter = &self.rl.helper().unwrap().highlighter;
-                let highlighted = if output.supports_color() {
-                    highlighter.highlight(&formatted)?
-                } else {
-                    formatted
-                };
-                writeln!(&mut output, "{}", highlighted)?;
+                self.print_sql(request)?;
             }
+            self.last_query = Some(request.to_string());
 
             // A single input may contain multiple SQL statements. Parse them
             // out and execute individually.
             let tree = crate::sql::parse_sql(request)?;
             for stmt_node in tree.statements() {
                 let sql = &request[stmt_node.byte_range()];
-                let kind = stmt_node.child(0).map(|node| node.kind());
-                match kind {
-                    Some("update_stmt" | "delete_stmt" | "insert_stmt") => {
-                        self.execute_update_query(sql)?
+                match crate::sql::explain_kind(stmt_node, request) {
+                    ExplainKind::QueryPlan => self.execute_query_plan(sql)?,
+                    ExplainKind::Explain => self.execute_statement(sql)?,
+                    ExplainKind::None => {
+                        let kind = stmt_node.child(0).map(|node| node.kind());
+                        let is_change =
+                            matches!(kind, Some("update_stmt" | "delete_stmt" | "insert_stmt"));
+                        // Other statements, like CREATE or PRAGMA, have no interesting plan.
+                        if self.eqp && (is_change || kind == Some("select_stmt")) {
+                            // Prepare the statement by itself first, so syntax errors point at
+                            // the SQL that was typed rather than the EXPLAIN around it.
+                            self.prepare(&self.conn, sql)?;
+                            self.execute_query_plan(&format!("EXPLAIN QUERY PLAN {}", sql))?;
+                        }
+                        self.execute_statement(sql)?;
                     }
-                    Some(
-                        "create_index_stmt"
-                        | "create_table_stmt"
-                        | "create_trigger_stmt"
-                        | "create_view_stmt"
-                        | "create_virtual_table_stmt"
-                        | "drop_index_stmt"
-                        | "drop_table_stmt"
-                        | "drop_trigger_stmt"
-                        | "drop_view_stmt",
-                    ) => self.execute_silent_query(sql)?,
-                    Some(_) | None => self.execute_select_query(sql)?,
                 }
             }
             Ok(())
@@ -155,7 +490,7 @@ impl App {
     }
 
     fn execute_dot_command(&mut self, request: &str) -> anyhow::Result<()> {
-        let clap_args = request.splitn(2, ' ');
+        let clap_args = split_dot_command(request);
 
         match DotCommand::try_parse_from(clap_args) {
             Ok(DotCommand::Help { subcommand: None }) => {
@@ -186,6 +521,44 @@ impl App {
                 self.output_mode = output_mode;
                 Ok(())
             }
+            Ok(DotCommand::Eqp { enabled }) => {
+                self.eqp = enabled.into();
+                Ok(())
+            }
+            Ok(DotCommand::Timer { enabled }) => {
+                self.timer = enabled.into();
+                Ok(())
+            }
+            Ok(DotCommand::Stats { enabled }) => {
+                self.stats = enabled.into();
+                Ok(())
+            }
+            Ok(DotCommand::Headers { enabled }) => {
+                self.headers = enabled.into();
+                Ok(())
+            }
+            Ok(DotCommand::Maxrows { limit }) => {
+                self.max_rows = limit;
+                Ok(())
+            }
+            Ok(DotCommand::Pager { mode }) => {
+                self.pager = mode;
+                Ok(())
+            }
+            Ok(DotCommand::Theme { name: None }) => {
+                let current = &self.rl.helper().unwrap().highlighter.theme().name;
+                let mut output = self.output_target.start(self.pager);
+                for name in theme::theme_names() {
+                    let marker = if &name == current { "*" } else { " " };
+                    writeln!(output, "{marker} {name}")?;
+                }
+                Ok(())
+            }
+            Ok(DotCommand::Theme { name: Some(name) }) => {
+                let theme = theme::Theme::load(&name)?;
+                self.rl.helper_mut().unwrap().highlighter.set_theme(theme);
+                Ok(())
+            }
             Ok(DotCommand::Output { filename: None }) => {
                 self.output_target =
                     OutputTarget::Stdout(StandardStream::stdout(ColorChoice::Auto));
@@ -198,10 +571,33 @@ impl App {
                 Ok(())
             }
             Ok(DotCommand::Schema { table_name }) => self.execute_schema(&table_name),
+            Ok(DotCommand::Format {
+                indent,
+                tabs,
+                uppercase,
+                lines,
+                sql,
+            }) => {
+                if let Some(indent) = indent {
+                    self.format_options.indent = Indent::Spaces(indent);
+                }
+                if tabs {
+                    self.format_options.indent = Indent::Tabs;
+                }
+                if let Some(uppercase) = uppercase {
+                    self.format_options.uppercase = uppercase.into();
+                }
+                if let Some(lines) = lines {
+                    self.format_options.lines_between_queries = lines;
+                }
+                let changed_options =
+                    indent.is_some() || tabs || uppercase.is_some() || lines.is_some();
+                self.execute_format(sql, changed_options)
+            }
             Ok(DotCommand::Parse { sql }) => {
                 let tree = crate::sql::parse_sql(&sql)?;
                 writeln!(
-                    self.output_target.start(),
+                    self.output_target.start(self.pager),
                     "{}",
                     tree.tree.root_node().to_sexp()
                 )?;
@@ -209,6 +605,25 @@ impl App {
             }
             Ok(DotCommand::Dump { filter }) => self.execute_dump(filter.as_deref()),
             Ok(DotCommand::Read { filename }) => self.execute_read(&filename),
+            Ok(DotCommand::Import {
+                filename,
+                table_name,
+                delimiter,
+                header: _,
+                no_header,
+                skip,
+            }) => {
+                let options = import::ImportOptions {
+                    delimiter,
+                    header: !no_header,
+                    skip,
+                };
+                let _running = self.interrupt.start();
+                let count = import::import(&self.conn, &filename, &table_name, &options)?;
+                println!("{} rows imported", count);
+                Ok(())
+            }
+            Ok(DotCommand::Param { action }) => self.execute_param(action),
             Ok(DotCommand::Backup { filename }) => self.execute_backup(&filename),
             Err(err) => {
                 err.print()?;
@@ -219,7 +634,7 @@ impl App {
 
     /// Execute a .tables command.
     fn execute_tables(&mut self) -> anyhow::Result<()> {
-        let mut output = self.output_target.start();
+        let mut output = self.output_target.start(self.pager);
 
         let mut stmt = self
             .conn
@@ -234,94 +649,159 @@ impl App {
 
     /// Execute a .schema command.
     fn execute_schema(&mut self, table_name: &str) -> anyhow::Result<()> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT sql FROM sqlite_schema WHERE type = 'table' AND name = ?")?;
-        let mut query = stmt.query([table_name])?;
-        let row = if let Some(row) = query.next()? {
-            row
-        } else {
-            anyhow::bail!("table {} does not exist", table_name);
+        let sql = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT sql FROM sqlite_schema WHERE type = 'table' AND name = ?")?;
+            let mut query = stmt.query([table_name])?;
+            let row = if let Some(row) = query.next()? {
+                row
+            } else {
+                anyhow::bail!("table {} does not exist", table_name);
+            };
+
+            let value_ref = row.get_ref(0)?;
+            if let ValueRef::Text(text) = value_ref {
+                std::str::from_utf8(text)?.to_string()
+            } else {
+                anyhow::bail!("sqlite_schema table does not contain `text` for some reason?");
+            }
         };
 
-        let value_ref = row.get_ref(0)?;
-        let sql = if let ValueRef::Text(text) = value_ref {
-            std::str::from_utf8(text)?
+        self.print_sql(&sql)
+    }
+
+    /// Execute a .format command. Without SQL, the last query is formatted, unless the command
+    /// only changes options.
+    fn execute_format(&mut self, sql: Vec<String>, changed_options: bool) -> anyhow::Result<()> {
+        if !sql.is_empty() {
+            self.print_sql(&sql.join(" "))
+        } else if changed_options {
+            Ok(())
         } else {
-            anyhow::bail!("sqlite_schema table does not contain `text` for some reason?");
-        };
+            let query = self
+                .last_query
+                .clone()
+                .context("there is no query to format yet")?;
+            self.print_sql(&query)
+        }
+    }
 
+    /// Format SQL with the `.format` options, and print it highlighted.
+    fn print_sql(&mut self, sql: &str) -> anyhow::Result<()> {
+        let formatted = format::format_sql(sql, &self.format_options);
+        let mut output = self.output_target.start(self.pager);
         let highlighter = &self.rl.helper().unwrap().highlighter;
-        let formatted = sqlformat::format(sql, &Default::default(), Default::default());
-
-        let mut output = self.output_target.start();
         let highlighted = if output.supports_color() {
             highlighter.highlight(&formatted)?
         } else {
             formatted
         };
         writeln!(&mut output, "{}", highlighted)?;
-
         Ok(())
     }
 
     fn execute_dump(&mut self, filter: Option<&str>) -> anyhow::Result<()> {
-        let mut output = self.output_target.start();
-
-        let mut tables_stmt = self.conn.prepare_cached(
-            "SELECT name, sql FROM sqlite_schema WHERE type = 'table' AND tbl_name LIKE ?",
-        )?;
-        let mut tables_query = tables_stmt.query([filter
-            .map(|name| format!("%{}%", name))
-            .unwrap_or_else(|| "%".to_string())])?;
-        let mut opt_row = if let Some(row) = tables_query.next()? {
-            Some(row)
-        } else {
-            anyhow::bail!("no results for {}", filter.unwrap_or(""));
-        };
-
         let highlighter = &self.rl.helper().unwrap().highlighter;
-        writeln!(
-            &mut output,
-            "{}",
-            highlighter.highlight("PRAGMA foreign_keys=OFF;")?
-        )?;
-        writeln!(
+        let mut output = self.output_target.start(self.pager);
+        let _running = self.interrupt.start();
+        dump::dump(
+            &self.conn,
+            highlighter,
+            &self.format_options,
+            filter,
             &mut output,
-            "{}",
-            highlighter.highlight("BEGIN TRANSACTION;")?
-        )?;
-
-        while let Some(row) = opt_row {
-            let name: String = row.get_unwrap(0);
-            let sql: String = row.get_unwrap(1);
-
-            let mut formatted = sqlformat::format(&sql, &Default::default(), Default::default());
-            formatted.push(';');
-            writeln!(&mut output, "{}", highlighter.highlight(&formatted)?)?;
+        )
+    }
 
-            let mut rows_stmt = self.conn.prepare(&format!("SELECT * FROM {}", &name))?;
+    /// Execute a .read command. Scripts may read other scripts, but not themselves, and stop at
+    /// the first error.
+    fn execute_read(&mut self, filename: &Path) -> anyhow::Result<()> {
+        let path = filename
+            .canonicalize()
+            .with_context(|| format!("could not read {}", filename.display()))?;
+        if self.read_stack.contains(&path) {
+            anyhow::bail!("{} is already being read", filename.display());
+        }
+        let script = std::fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", filename.display()))?;
 
-            let mut output_rows =
-                SqlOutput::new(&rows_stmt, highlighter, &mut output).with_table_name(name);
+        self.read_stack.push(path);
+        let result = split_script(&script)
+            .into_iter()
+            .try_for_each(|(line, command)| {
+                self.execute(&command)
+                    .with_context(|| format!("{}:{}", filename.display(), line))
+            });
+        self.read_stack.pop();
+        result
+    }
 
-            let mut rows_query = rows_stmt.query([])?;
-            while let Some(row) = rows_query.next()? {
-                output_rows.add_row(row)?;
+    /// Execute commands given on the command line, and return whether all of them succeeded.
+    /// Errors are printed, and stop the remaining commands if `bail` is set.
+    fn execute_all(&mut self, commands: &[String], bail: bool) -> bool {
+        let mut success = true;
+        for command in commands {
+            if let Err(err) = self.execute(command) {
+                eprintln!("Error: {:?}", err);
+                success = false;
+                if bail {
+                    break;
+                }
             }
-            output_rows.finish()?;
+        }
+        success
+    }
 
-            opt_row = tables_query.next()?;
+    /// Execute a script of dot commands and SQL, like an init file, and return whether all
+    /// commands succeeded. Errors are printed with the line they come from, and stop the rest of
+    /// the script if `bail` is set.
+    fn execute_script(&mut self, script: &str, source: &str, bail: bool) -> bool {
+        let mut success = true;
+        for (line, command) in split_script(script) {
+            if let Err(err) = self.execute(&command) {
+                eprintln!("Error: {:?}", err.context(format!("{}:{}", source, line)));
+                success = false;
+                if bail {
+                    break;
+                }
+            }
         }
+        success
+    }
 
-        writeln!(&mut output, "{}", highlighter.highlight("COMMIT;")?)?;
+    /// Execute a .param command.
+    fn execute_param(&mut self, action: ParamAction) -> anyhow::Result<()> {
+        match action {
+            ParamAction::Set { name, value } => {
+                let value = crate::sql::parse_literal(&value.join(" "));
+                self.parameters.insert(name, value);
+            }
+            ParamAction::Unset { name } => {
+                if self.parameters.remove(&name).is_none() {
+                    anyhow::bail!("parameter {} is not set", name);
+                }
+            }
+            ParamAction::List => {
+                let mut output = self.output_target.start(self.pager);
+                for (name, value) in &self.parameters {
+                    let mut literal = String::new();
+                    crate::sql::write_sql_literal(value.into(), &mut literal);
+                    writeln!(&mut output, "{} = {}", name, literal)?;
+                }
+            }
+            ParamAction::Clear => self.parameters.clear(),
+        }
         Ok(())
     }
 
-    fn execute_read(&mut self, filename: &Path) -> anyhow::Result<()> {
-        let sql = std::fs::read_to_string(filename)?;
-        self.execute(&sql)?;
-        Ok(())
+    /// Get the value for a bind parameter set with `.param`. Names set without a prefix match
+    /// parameters with any prefix.
+    fn get_parameter(&self, name: &str) -> Option<&Value> {
+        self.parameters.get(name).or_else(|| {
+            name.strip_prefix([':', '@', '$', '?'])
+                .and_then(|bare_name| self.parameters.get(bare_name))
+        })
     }
 
     fn execute_backup(&mut self, filename: &Path) -> anyhow::Result<()> {
@@ -331,8 +811,13 @@ impl App {
         let mut destination = Connection::open(filename)?;
         let backup = Backup::new(&self.conn, &mut destination)?;
 
+        let running = self.interrupt.start();
         let bar = ProgressBar::new(0);
         while backup.step(100)? != StepResult::Done {
+            // Backups are not interrupted by SQLite, so stop between steps.
+            if running.is_interrupted() {
+                anyhow::bail!("interrupted");
+            }
             let progress = backup.progress();
             bar.set_length(progress.pagecount.try_into().unwrap());
             bar.set_position(
@@ -345,21 +830,42 @@ impl App {
         Ok(())
     }
 
-    fn prompt_bind_parameters(
-        &mut self,
-        stmt: &mut Statement<'_>,
-    ) -> anyhow::Result<Vec<Box<dyn ToSql>>> {
+    /// Ask for values for the parameters in a statement. Values are parsed as SQL literals, or
+    /// used as text if they are not valid literals. The previous value of named parameters is
+    /// offered as the default.
+    fn prompt_bind_parameters(&mut self, stmt: &mut Statement<'_>) -> anyhow::Result<Vec<Value>> {
+        // Values are not SQL statements, so they do not need a terminating `;`.
+        self.rl.helper().unwrap().set_validate_statements(false);
+        let values = self.read_bind_parameters(stmt);
+        self.rl.helper().unwrap().set_validate_statements(true);
+        values
+    }
+
+    fn read_bind_parameters(&mut self, stmt: &mut Statement<'_>) -> anyhow::Result<Vec<Value>> {
         let parameter_count = stmt.parameter_count();
-        let mut values: Vec<Box<dyn ToSql>> = Vec::with_capacity(parameter_count);
+        let mut values = Vec::with_capacity(parameter_count);
         for index in 1..(parameter_count + 1) {
-            let prompt = if let Some(name) = stmt.parameter_name(index) {
+            let name = stmt.parameter_name(index);
+            if let Some(value) = name.and_then(|name| self.get_parameter(name)) {
+                values.push(value.clone());
+                continue;
+            }
+            let prompt = if let Some(name) = name {
                 format!("{name}: ")
             } else {
                 format!("?{}: ", index)
             };
-            let readline = self.rl.readline(&prompt);
+            let default = name
+                .and_then(|name| self.parameter_values.get(name))
+                .map_or("", String::as_str);
+            let readline = self.rl.readline_with_initial(&prompt, (default, ""));
             match readline {
-                Ok(value) => values.push(Box::new(value)),
+                Ok(input) => {
+                    values.push(crate::sql::parse_literal(&input));
+                    if let Some(name) = name {
+                        self.parameter_values.insert(name.to_string(), input);
+                    }
+                }
                 Err(ReadlineError::Interrupted | ReadlineError::Eof) => {
                     return Err(anyhow::Error::msg("Cancelled query"))
                 }
@@ -370,48 +876,162 @@ impl App {
         Ok(values)
     }
 
-    /// Execute an UPDATE, DELETE or INSERT query.
-    fn execute_update_query(&mut self, sql: &str) -> anyhow::Result<()> {
+    /// Prepare a statement. Errors point at the part of the statement that caused them, if
+    /// possible.
+    fn prepare<'c>(&self, conn: &'c Connection, sql: &str) -> anyhow::Result<Statement<'c>> {
+        conn.prepare(sql).map_err(|err| {
+            let completions = self.rl.helper().unwrap().completions();
+            match diagnostics::diagnose(&err, sql, completions) {
+                Some(diagnostic) => diagnostic.into(),
+                None => err.into(),
+            }
+        })
+    }
+
+    /// Execute a statement. Rows are printed if the statement returns columns, like SELECT,
+    /// PRAGMA or `INSERT ... RETURNING` do. The number of changed rows is printed for every
+    /// statement that can write to the database, so CREATE TABLE reports 0 changes.
+    fn execute_statement(&mut self, sql: &str) -> anyhow::Result<()> {
         let conn = self.conn.clone();
-        let mut stmt = conn.prepare(sql)?;
+        let mut stmt = self.prepare(&conn, sql)?;
         let values = self.prompt_bind_parameters(&mut stmt)?;
 
-        let changes = stmt.execute(rusqlite::params_from_iter(values))?;
-        println!("{} changes", changes);
+        // `changes()` keeps the count of the last INSERT, UPDATE or DELETE, even if this
+        // statement is something else, so count the changes made while it runs instead.
+        let changes_before = total_changes(&conn)?;
+        let (num_rows, elapsed) = if stmt.column_count() > 0 {
+            let (num_rows, elapsed) = self.print_rows(&mut stmt, values)?;
+            (Some(num_rows), elapsed)
+        } else {
+            let _running = self.interrupt.start();
+            let start = Instant::now();
+            stmt.execute(rusqlite::params_from_iter(values))?;
+            (None, start.elapsed())
+        };
 
+        // Read-only statements, like SELECT or EXPLAIN, never change anything.
+        let changes = if stmt.readonly() {
+            None
+        } else {
+            Some(total_changes(&conn)? - changes_before)
+        };
+        if let Some(changes) = changes {
+            println!("{} changes", changes);
+        }
+        match num_rows {
+            Some(num_rows) => self.print_statistics(&stmt, elapsed, "returned", num_rows),
+            None => {
+                let changes = changes.unwrap_or(0) as usize;
+                self.print_statistics(&stmt, elapsed, "changed", changes)
+            }
+        }
         Ok(())
     }
 
-    /// Execute a query that does not return anything.
-    fn execute_silent_query(&mut self, sql: &str) -> anyhow::Result<()> {
+    /// Execute an EXPLAIN QUERY PLAN statement, and print the plan as a tree. Bind parameters are
+    /// left unset, because they do not change the plan.
+    fn execute_query_plan(&mut self, sql: &str) -> anyhow::Result<()> {
         let conn = self.conn.clone();
-        let mut stmt = conn.prepare(sql)?;
-        let values = self.prompt_bind_parameters(&mut stmt)?;
-
-        let _ = stmt.execute(rusqlite::params_from_iter(values))?;
-
+        let mut stmt = self.prepare(&conn, sql)?;
+        let steps = {
+            let _running = self.interrupt.start();
+            query_plan::query_plan(&mut stmt)?
+        };
+        if !steps.is_empty() {
+            let mut output = self.output_target.start(self.pager);
+            let theme = self.rl.helper().unwrap().highlighter.theme();
+            query_plan::write_query_plan(&steps, theme, &mut output)?;
+        }
         Ok(())
     }
 
-    /// Execute a SELECT query.
-    fn execute_select_query(&mut self, sql: &str) -> anyhow::Result<()> {
-        let conn = self.conn.clone();
-        let mut stmt = conn.prepare(sql)?;
-        let values = self.prompt_bind_parameters(&mut stmt)?;
+    /// Print the statistics turned on with `.timer` and `.stats` for a finished statement.
+    fn print_statistics(
+        &self,
+        stmt: &Statement<'_>,
+        elapsed: Duration,
+        rows: &str,
+        num_rows: usize,
+    ) {
+        if self.timer {
+            println!("Run time: {:.3?}", elapsed);
+        }
+        if self.stats {
+            println!(
+                "Rows {}: {}, full scan steps: {}, sorts: {}, autoindexes: {}, VM steps: {}",
+                rows,
+                num_rows,
+                stmt.get_status(StatementStatus::FullscanStep),
+                stmt.get_status(StatementStatus::Sort),
+                stmt.get_status(StatementStatus::AutoIndex),
+                stmt.get_status(StatementStatus::VmStep),
+            );
+        }
+    }
 
+    /// Print the rows returned by a statement in the current output mode, and return the number
+    /// of rows, including the ones left out by `.maxrows`, and how long the query ran. Time spent
+    /// in the pager is not included.
+    fn print_rows(
+        &mut self,
+        stmt: &mut Statement<'_>,
+        values: Vec<Value>,
+    ) -> anyhow::Result<(usize, Duration)> {
         let highlighter = &self.rl.helper().unwrap().highlighter;
-        let mut output = self.output_target.start();
-        let mut output_rows = self
-            .output_mode
-            .output_rows(&stmt, highlighter, &mut output);
+        let terminal_width = self.output_target.terminal_width();
+        let mut output = self.output_target.start(self.pager);
+        let mut output_rows = self.output_mode.output_rows(
+            stmt,
+            highlighter,
+            terminal_width,
+            self.headers,
+            &mut output,
+        );
 
+        let running = self.interrupt.start();
+        let start = Instant::now();
         let mut query = stmt.query(rusqlite::params_from_iter(values))?;
+        let mut num_rows = 0;
+        // Stop printing at the first row over the limit. The rest is counted below, after the
+        // printed rows are shown.
+        let mut more_rows = false;
         while let Some(row) = query.next()? {
+            if self.max_rows > 0 && num_rows == self.max_rows {
+                more_rows = true;
+                break;
+            }
             output_rows.add_row(row)?;
+            num_rows += 1;
         }
+        let mut elapsed = start.elapsed();
         output_rows.finish()?;
+        drop(output_rows);
+        drop(output);
 
-        Ok(())
+        if more_rows {
+            // Counting can take a while for big results, so Ctrl-C skips it.
+            let start = Instant::now();
+            let mut num_left_out = 1;
+            let counted = loop {
+                match query.next() {
+                    Ok(Some(_)) => num_left_out += 1,
+                    Ok(None) => break true,
+                    Err(_) if running.is_interrupted() => break false,
+                    Err(err) => return Err(err.into()),
+                }
+            };
+            elapsed += start.elapsed();
+            if counted {
+                eprintln!(
+                    "... {} more rows not shown (.maxrows {})",
+                    num_left_out, self.max_rows
+                );
+                num_rows += num_left_out;
+            } else {
+                eprintln!("... more rows not shown (.maxrows {})", self.max_rows);
+            }
+        }
+        Ok((num_rows, elapsed))
     }
 }
 
@@ -421,6 +1041,37 @@ struct Opts {
     filename: Option<PathBuf>,
     /// Queries to execute on the database. If omitted, sqc enters interactive mode.
     queries: Vec<String>,
+    /// Dot commands and SQL to execute before anything else. Defaults to the sqcrc file in the
+    /// config directory, like `~/.config/sqc/sqcrc`.
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    init: Option<PathBuf>,
+    /// A dot command or SQL to execute before the queries. May be given multiple times.
+    #[arg(long = "cmd", value_name = "COMMAND")]
+    commands: Vec<String>,
+    /// The output format/mode. Defaults to `table`.
+    #[arg(long, value_enum)]
+    mode: Option<OutputMode>,
+    /// Send output to a file, instead of stdout.
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+    /// Open the database read-only.
+    #[arg(long)]
+    readonly: bool,
+    /// Print SQL statements before executing them.
+    #[arg(long)]
+    echo: bool,
+    /// Print column names in csv, markdown and html output. This is the default.
+    #[arg(long, overrides_with = "no_header")]
+    header: bool,
+    /// Leave out column names in csv, markdown and html output.
+    #[arg(long, overrides_with = "header")]
+    no_header: bool,
+    /// Stop at the first query that fails. This is the default.
+    #[arg(long, overrides_with = "no_bail")]
+    bail: bool,
+    /// Keep executing queries after one fails. The exit code is still non-zero.
+    #[arg(long, overrides_with = "bail")]
+    no_bail: bool,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -434,32 +1085,55 @@ fn main() -> anyhow::Result<()> {
         let _ = std::fs::create_dir_all(dirs.data_dir());
     }
 
+    let flags = if opts.readonly {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX
+    } else {
+        OpenFlags::default()
+    };
     let conn = Rc::new(match &opts.filename {
-        Some(filename) => Connection::open(filename)?,
-        None => Connection::open_in_memory()?,
+        Some(filename) => Connection::open_with_flags(filename, flags)?,
+        None => Connection::open_in_memory_with_flags(flags)?,
     });
 
     rusqlite::vtab::csvtab::load_module(&conn)?;
     functions::install(&conn)?;
 
-    let completions = Completions::new(Rc::clone(&conn));
-
-    let mut rl = Editor::new()?;
-    rl.set_helper(Some(EditorHelper::new(
-        opts.filename
-            .and_then(|f| f.file_name().map(|os| os.to_string_lossy().to_string())),
-        completions,
-    )));
-
-    let mut app = App {
-        rl,
-        conn,
-        output_target: OutputTarget::Stdout(StandardStream::stdout(ColorChoice::Auto)),
-        output_mode: OutputMode::Table,
-        echo: false,
-    };
+    let name = opts
+        .filename
+        .as_ref()
+        .and_then(|f| f.file_name().map(|os| os.to_string_lossy().to_string()));
+    let mut app = App::new(conn, name)?;
+    let interactive = opts.queries.is_empty() && std::io::stdin().is_terminal();
+    app.interrupt.install(interactive)?;
+
+    // The default init file is optional, but one given on the command line must exist.
+    let init_path = opts.init.clone().or_else(|| {
+        dirs.as_ref()
+            .map(|dirs| dirs.config_dir().join("sqcrc"))
+            .filter(|path| path.exists())
+    });
+    if let Some(path) = &init_path {
+        let script = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        app.execute_script(&script, &path.display().to_string(), false);
+    }
+    app.apply_flags(&opts)?;
+
+    let bail = !opts.no_bail;
+    let mut success = app.execute_all(&opts.commands, bail);
+    if !success && bail {
+        std::process::exit(1);
+    }
 
-    if opts.queries.is_empty() {
+    if !opts.queries.is_empty() {
+        success &= app.execute_all(&opts.queries, bail);
+    } else if !std::io::stdin().is_terminal() {
+        // Piped input is a script, like `cat migration.sql | sqc app.db`.
+        let script = std::io::read_to_string(std::io::stdin())?;
+        success &= app.execute_script(&script, "stdin", bail);
+    } else {
         if let Some(path) = &history_path {
             let _ = app.rl.load_history(path);
         } else {
@@ -469,11 +1143,147 @@ fn main() -> anyhow::Result<()> {
         if let Some(path) = &history_path {
             let _ = app.rl.save_history(path);
         }
-    } else {
-        for query in opts.queries {
-            app.execute(&query)?;
-        }
     }
 
+    if !success {
+        std::process::exit(1);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_with_uppercase_keywords() {
+        let conn = Rc::new(Connection::open_in_memory().unwrap());
+        conn.execute_batch(
+            "CREATE TABLE t (status TEXT, type TEXT, key TEXT, name TEXT);
+            INSERT INTO t VALUES ('a', 'b', 'c', 'd');
+            CREATE VIEW v AS select status,   type FROM t;",
+        )
+        .unwrap();
+        let path = std::env::temp_dir().join(format!("sqc-dump-{}.sql", std::process::id()));
+        let mut app = App::new(Rc::clone(&conn), None).unwrap();
+        app.output_target = OutputTarget::File(std::fs::File::create(&path).unwrap());
+        app.execute(".format --uppercase on").unwrap();
+        app.execute(".dump").unwrap();
+        let script = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(script.contains("CREATE VIEW v AS\nSELECT status, type\nFROM t;"));
+        let restored = Connection::open_in_memory().unwrap();
+        restored.execute_batch(&script).unwrap();
+        let row: (String, String, String, String) = restored
+            .query_row("SELECT * FROM t", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap();
+        assert_eq!(row, ("a".into(), "b".into(), "c".into(), "d".into()));
+    }
+
+    #[test]
+    fn flags_override_init_file() {
+        let init = ".mode csv\n.echo off\n.headers off\n";
+        let conn = Rc::new(Connection::open_in_memory().unwrap());
+        let mut app = App::new(conn, None).unwrap();
+        app.execute_script(init, "sqcrc", false);
+        app.apply_flags(&Opts::parse_from([
+            "sqc", "--mode", "table", "--echo", "--header",
+        ]))
+        .unwrap();
+        assert_eq!(app.output_mode, OutputMode::Table);
+        assert!(app.echo);
+        assert!(app.headers);
+
+        // Without flags, the init file's settings are kept.
+        let conn = Rc::new(Connection::open_in_memory().unwrap());
+        let mut app = App::new(conn, None).unwrap();
+        app.execute_script(init, "sqcrc", false);
+        app.apply_flags(&Opts::parse_from(["sqc"])).unwrap();
+        assert_eq!(app.output_mode, OutputMode::Csv);
+        assert!(!app.echo);
+        assert!(!app.headers);
+    }
+
+    #[test]
+    fn split_dot_commands() {
+        assert_eq!(split_dot_command(".tables"), vec![".tables"]);
+        assert_eq!(
+            split_dot_command(".parse SELECT  'a b'"),
+            vec![".parse", "SELECT  'a b'"]
+        );
+        assert_eq!(
+            split_dot_command(".read my file.sql"),
+            vec![".read", "my file.sql"]
+        );
+        assert_eq!(
+            split_dot_command(".import 'my file.csv' data --delimiter ';' --skip 2"),
+            vec![
+                ".import",
+                "my file.csv",
+                "data",
+                "--delimiter",
+                ";",
+                "--skip",
+                "2"
+            ]
+        );
+        assert_eq!(
+            split_dot_command(".param set :name 'it''s a string'"),
+            vec![".param", "set", ":name", "'it''s a string'"]
+        );
+        assert_eq!(
+            split_dot_command(".param unset :name"),
+            vec![".param", "unset", ":name"]
+        );
+        assert_eq!(
+            split_dot_command(".format --indent 4 --uppercase on SELECT a -- the a\nFROM t"),
+            vec![
+                ".format",
+                "--indent",
+                "4",
+                "--uppercase",
+                "on",
+                "SELECT a -- the a\nFROM t"
+            ]
+        );
+        assert!(matches!(
+            DotCommand::try_parse_from(split_dot_command(".format --tabs")),
+            Ok(DotCommand::Format { tabs: true, sql, .. }) if sql.is_empty()
+        ));
+        assert!(matches!(
+            DotCommand::try_parse_from(split_dot_command(".param set n -1")),
+            Ok(DotCommand::Param {
+                action: ParamAction::Set { value, .. }
+            }) if value == ["-1"]
+        ));
+    }
+
+    #[test]
+    fn split_scripts() {
+        let script = ".mode csv\n\nSELECT 1;\n  .tables\nSELECT\n  2;\n";
+        assert_eq!(
+            split_script(script),
+            vec![
+                (1, ".mode csv".to_string()),
+                (3, "SELECT 1;\n".to_string()),
+                (4, ".tables".to_string()),
+                (5, "SELECT\n  2;\n".to_string()),
+            ]
+        );
+
+        let script = "CREATE TRIGGER log AFTER INSERT ON users BEGIN\n  INSERT INTO log VALUES (1);\n  UPDATE log SET id = 2;\nEND;\nSELECT 1;\n";
+        assert_eq!(
+            split_script(script),
+            vec![
+                (
+                    1,
+                    "CREATE TRIGGER log AFTER INSERT ON users BEGIN\n  INSERT INTO log VALUES (1);\n  UPDATE log SET id = 2;\nEND;\n".to_string()
+                ),
+                (5, "SELECT 1;\n".to_string()),
+            ]
+        );
+    }
+}
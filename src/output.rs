@@ -1,14 +1,18 @@
 use crate::highlight::SqlHighlighter;
-use comfy_table::{Cell, Color, ContentArrangement, Table};
+use crate::interrupt::RunningPager;
+use crate::sql::{quote_identifier, write_sql_literal};
+use crate::theme::Theme;
+use comfy_table::{Attribute, Cell, Color, ContentArrangement, Table};
 use csv::{ByteRecord, Writer, WriterBuilder};
-use rusqlite::types::ValueRef;
+use rusqlite::types::{Value, ValueRef};
 use rusqlite::{Row, Statement};
+use std::borrow::Cow;
 use std::fmt::Write as _;
 use std::fs::File;
-use std::io::Write;
-use std::process::{Command, Stdio};
+use std::io::{IsTerminal as _, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
 use std::str::FromStr;
-use termcolor::{StandardStream, WriteColor};
+use termcolor::{Ansi, Buffer, ColorSpec, StandardStream, StandardStreamLock, WriteColor};
 
 pub enum OutputTarget {
     Stdout(StandardStream),
@@ -16,14 +20,182 @@ pub enum OutputTarget {
 }
 
 impl OutputTarget {
-    pub fn start(&mut self) -> Box<dyn WriteColor + '_> {
+    /// Get the width of the terminal the output goes to, or `None` for files and pipes.
+    pub fn terminal_width(&self) -> Option<usize> {
         match self {
-            OutputTarget::Stdout(stream) => Box::new(stream.lock()),
+            OutputTarget::Stdout(_) if std::io::stdout().is_terminal() => console::Term::stdout()
+                .size_checked()
+                .map(|(_, columns)| usize::from(columns)),
+            _ => None,
+        }
+    }
+
+    /// Start writing output. Output to a terminal goes through a pager, depending on `pager`.
+    pub fn start(&mut self, pager: PagerMode) -> Box<dyn WriteColor + '_> {
+        match self {
+            OutputTarget::Stdout(stream) => {
+                let max_lines = match pager {
+                    PagerMode::On => Some(0),
+                    PagerMode::Off => None,
+                    // Leave room for the prompt.
+                    PagerMode::Auto => console::Term::stdout()
+                        .size_checked()
+                        .map(|(rows, _)| usize::from(rows).saturating_sub(1)),
+                };
+                match max_lines {
+                    Some(max_lines) if std::io::stdout().is_terminal() => {
+                        Box::new(PagedOutput::new(stream.lock(), max_lines))
+                    }
+                    _ => Box::new(stream.lock()),
+                }
+            }
             OutputTarget::File(file) => Box::new(WriteColorFile(file)),
         }
     }
 }
 
+/// When to send output to a pager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PagerMode {
+    /// Always use a pager
+    On,
+    /// Never use a pager
+    Off,
+    /// Use a pager if the output does not fit in the terminal
+    Auto,
+}
+
+/// Writes to stdout, or to a pager once the output has more than `max_lines` lines.
+///
+/// Output is buffered until it is known which one is used. The pager is `$PAGER`, or `less`.
+struct PagedOutput<'a> {
+    stdout: StandardStreamLock<'a>,
+    max_lines: usize,
+    num_lines: usize,
+    buffer: Buffer,
+    pager: Option<(Child, Ansi<ChildStdin>)>,
+    /// Whether output goes straight to stdout, because the pager could not be started.
+    unpaged: bool,
+    /// Dropped after the pager exits, see `Drop`.
+    _running_pager: Option<RunningPager>,
+}
+
+impl<'a> PagedOutput<'a> {
+    fn new(stdout: StandardStreamLock<'a>, max_lines: usize) -> Self {
+        let buffer = if stdout.supports_color() {
+            Buffer::ansi()
+        } else {
+            Buffer::no_color()
+        };
+        Self {
+            stdout,
+            max_lines,
+            num_lines: 0,
+            buffer,
+            pager: None,
+            unpaged: false,
+            _running_pager: None,
+        }
+    }
+
+    fn start_pager(&mut self) -> std::io::Result<()> {
+        let command = std::env::var("PAGER")
+            .ok()
+            .filter(|command| !command.trim().is_empty())
+            .unwrap_or_else(|| "less".to_string());
+        let mut args = command.split_whitespace();
+        let mut pager = Command::new(args.next().unwrap_or_default());
+        pager
+            .args(args)
+            .stdin(Stdio::piped())
+            .env("LESSCHARSET", "UTF-8");
+        // Keep colors, and do not page output that fits on one screen after all.
+        if std::env::var_os("LESS").is_none() {
+            pager.env("LESS", "FRX");
+        }
+
+        let Ok(mut child) = pager.spawn() else {
+            // Without a pager, everything goes to stdout, instead of being buffered until the end.
+            self.unpaged = true;
+            let result = self.stdout.write_all(self.buffer.as_slice());
+            self.buffer.clear();
+            return result;
+        };
+        self._running_pager = Some(RunningPager::start());
+        let mut input = Ansi::new(child.stdin.take().unwrap());
+        // The buffer is dropped even if the pager was quit already, so it is not printed later.
+        let result = input.write_all(self.buffer.as_slice());
+        self.buffer.clear();
+        self.pager = Some((child, input));
+        result
+    }
+}
+
+impl<'a> Write for PagedOutput<'a> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        if let Some((_, input)) = &mut self.pager {
+            return input.write(bytes);
+        }
+        if self.unpaged {
+            return self.stdout.write(bytes);
+        }
+        self.buffer.write_all(bytes)?;
+        self.num_lines += bytes.iter().filter(|&&byte| byte == b'\n').count();
+        if self.num_lines > self.max_lines {
+            self.start_pager()?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.pager {
+            Some((_, input)) => input.flush(),
+            None if self.unpaged => self.stdout.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> WriteColor for PagedOutput<'a> {
+    fn supports_color(&self) -> bool {
+        self.stdout.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &termcolor::ColorSpec) -> std::io::Result<()> {
+        match &mut self.pager {
+            Some((_, input)) if self.stdout.supports_color() => input.set_color(spec),
+            Some(_) => Ok(()),
+            None if self.unpaged => self.stdout.set_color(spec),
+            None => self.buffer.set_color(spec),
+        }
+    }
+
+    fn reset(&mut self) -> std::io::Result<()> {
+        match &mut self.pager {
+            Some((_, input)) if self.stdout.supports_color() => input.reset(),
+            Some(_) => Ok(()),
+            None if self.unpaged => self.stdout.reset(),
+            None => self.buffer.reset(),
+        }
+    }
+}
+
+impl<'a> Drop for PagedOutput<'a> {
+    fn drop(&mut self) {
+        match self.pager.take() {
+            Some((mut child, input)) => {
+                // Closing the input lets the pager know the output is complete.
+                drop(input);
+                let _ = child.wait();
+            }
+            None => {
+                let _ = self.stdout.write_all(self.buffer.as_slice());
+                let _ = self.stdout.flush();
+            }
+        }
+    }
+}
+
 struct WriteColorFile<'f>(&'f mut File);
 impl<'f> std::io::Write for WriteColorFile<'f> {
     fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
@@ -51,20 +223,50 @@ pub enum OutputMode {
     Table,
     Csv,
     Sql,
+    /// A single JSON array of objects. Blobs are encoded as base64 strings.
+    Json,
+    /// One JSON object per line. Blobs are encoded as base64 strings.
+    Ndjson,
+    /// A GitHub-flavoured Markdown pipe table.
+    Markdown,
+    /// An HTML `<table>` element.
+    Html,
+    /// One `column = value` line per column, with a blank line between rows.
+    #[value(alias = "vertical")]
+    Line,
+    /// A table, or `line` output if the table would be wider than the terminal.
+    Auto,
 }
 
 impl OutputMode {
+    /// Create the output for a statement's rows. Tables are fit to `terminal_width`, which is
+    /// `None` if the output does not go to a terminal.
     pub fn output_rows<'h>(
         self,
         statement: &Statement<'_>,
         highlight: &'h SqlHighlighter,
+        terminal_width: Option<usize>,
+        headers: bool,
         output: &'h mut dyn WriteColor,
     ) -> Box<dyn OutputRows + 'h> {
         match self {
             OutputMode::Null => Box::new(NullOutput),
-            OutputMode::Table => Box::new(TableOutput::new(statement, output)),
+            OutputMode::Table => Box::new(
+                TableOutput::new(statement, highlight.theme(), output)
+                    .with_terminal_width(terminal_width),
+            ),
             OutputMode::Sql => Box::new(SqlOutput::new(statement, highlight, output)),
-            OutputMode::Csv => Box::new(CsvOutput::new(statement, output)),
+            OutputMode::Csv => Box::new(CsvOutput::new(statement, headers, output)),
+            OutputMode::Json => Box::new(JsonOutput::new(statement, output)),
+            OutputMode::Ndjson => Box::new(NdjsonOutput::new(statement, output)),
+            OutputMode::Markdown => Box::new(MarkdownOutput::new(statement, headers, output)),
+            OutputMode::Html => Box::new(HtmlOutput::new(statement, headers, output)),
+            OutputMode::Line => Box::new(LineOutput::new(statement, output)),
+            OutputMode::Auto => Box::new(
+                TableOutput::new(statement, highlight.theme(), output)
+                    .with_terminal_width(terminal_width)
+                    .with_vertical_fallback(),
+            ),
         }
     }
 }
@@ -77,6 +279,12 @@ impl FromStr for OutputMode {
             "table" => Ok(Self::Table),
             "csv" => Ok(Self::Csv),
             "sql" => Ok(Self::Sql),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            "markdown" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            "line" | "vertical" => Ok(Self::Line),
+            "auto" => Ok(Self::Auto),
             _ => Err(()),
         }
     }
@@ -97,25 +305,246 @@ impl OutputRows for NullOutput {
     }
 }
 
+/// Tables are buffered until they have this many rows. Larger tables are streamed, with column
+/// widths based on the buffered rows.
+const STREAMING_ROWS: usize = 1000;
+
+/// Columns of streamed tables are not shrunk below this width to fit in the terminal.
+const MIN_STREAMING_WIDTH: usize = 8;
+
 pub struct TableOutput<'a> {
-    table: Table,
-    num_columns: usize,
+    column_names: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    theme: &'a Theme,
     output: &'a mut dyn WriteColor,
+    /// The width to fit the table to, if the output goes to a terminal.
+    terminal_width: Option<usize>,
+    /// Print rows in `line` format if the table does not fit in the terminal.
+    vertical_fallback: bool,
+    /// Set once the table has too many rows to buffer, and rows are printed as they arrive.
+    streaming: Option<Streaming>,
+}
+
+enum Streaming {
+    /// Rows are printed with fixed column widths, and cells that do not fit are truncated.
+    Table { widths: Vec<usize> },
+    /// Rows are printed in `line` format.
+    Lines { num_rows: usize },
 }
 
 impl<'a> TableOutput<'a> {
-    pub fn new(statement: &Statement<'_>, output: &'a mut dyn WriteColor) -> Self {
-        let mut table = Table::new();
-        table.load_preset("││──╞══╡│    ──┌┐└┘");
-        let names = statement.column_names();
-        let num_columns = names.len();
-        table.set_header(names);
+    pub fn new(
+        statement: &Statement<'_>,
+        theme: &'a Theme,
+        output: &'a mut dyn WriteColor,
+    ) -> Self {
         Self {
-            table,
-            num_columns,
+            column_names: owned_column_names(statement),
+            rows: vec![],
+            theme,
             output,
+            terminal_width: None,
+            vertical_fallback: false,
+            streaming: None,
+        }
+    }
+
+    /// Fit the table to a terminal this wide.
+    pub fn with_terminal_width(self, terminal_width: Option<usize>) -> Self {
+        Self {
+            terminal_width,
+            ..self
+        }
+    }
+
+    pub fn with_vertical_fallback(self) -> Self {
+        Self {
+            vertical_fallback: true,
+            ..self
         }
     }
+
+    fn build_table(&self) -> Table {
+        let mut table = Table::new();
+        table.load_preset("││──╞══╡│    ──┌┐└┘");
+        table.set_header(&self.column_names);
+        let color = self.output.supports_color();
+        for row in &self.rows {
+            table.add_row(row.iter().map(|value| {
+                if color {
+                    value_to_cell(value.into(), self.theme)
+                } else {
+                    value_to_cell_nocolor(value.into())
+                }
+            }));
+        }
+        table
+    }
+
+    /// Get the widest line in each column, of the header and the buffered rows.
+    fn content_widths(&self) -> Vec<usize> {
+        let mut widths = self
+            .column_names
+            .iter()
+            .map(|name| console::measure_text_width(name))
+            .collect::<Vec<_>>();
+        for row in &self.rows {
+            for (width, value) in widths.iter_mut().zip(row) {
+                let text = value_to_string(value.into());
+                let text_width = text.lines().map(console::measure_text_width).max();
+                *width = (*width).max(text_width.unwrap_or(0));
+            }
+        }
+        widths
+    }
+
+    /// Check if the table would be wider than the terminal. Always false if the output does not
+    /// go to a terminal, eg. when writing to a file or a pipe.
+    fn is_too_wide(&self) -> bool {
+        let Some(terminal_width) = self.terminal_width else {
+            return false;
+        };
+        table_width(&self.content_widths()) > terminal_width
+    }
+
+    fn write_vertical(&mut self) -> std::io::Result<()> {
+        for (index, row) in self.rows.iter().enumerate() {
+            let values = row
+                .iter()
+                .map(|value| value_to_string(value.into()))
+                .collect::<Vec<_>>();
+            write_line_record(&mut *self.output, &self.column_names, &values, index)?;
+        }
+        Ok(())
+    }
+
+    /// Print the buffered rows, and print further rows as they arrive.
+    fn start_streaming(&mut self) -> std::io::Result<()> {
+        if self.vertical_fallback && self.is_too_wide() {
+            self.write_vertical()?;
+            self.streaming = Some(Streaming::Lines {
+                num_rows: self.rows.len(),
+            });
+            self.rows.clear();
+            return Ok(());
+        }
+
+        // Shrink the widest columns until the table fits in the terminal.
+        let mut widths = self
+            .content_widths()
+            .into_iter()
+            .map(|width| width.max(1))
+            .collect::<Vec<_>>();
+        if let Some(terminal_width) = self.terminal_width {
+            while table_width(&widths) > terminal_width {
+                let Some(widest) = widths
+                    .iter_mut()
+                    .filter(|width| **width > MIN_STREAMING_WIDTH)
+                    .max()
+                else {
+                    break;
+                };
+                *widest -= 1;
+            }
+        }
+
+        writeln!(self.output, "{}", border(&widths, '┌', '─', '┐'))?;
+        let header = self
+            .column_names
+            .iter()
+            .map(|name| (name.as_str(), None))
+            .collect::<Vec<_>>();
+        write_streaming_row(&mut *self.output, &widths, &header)?;
+        writeln!(self.output, "{}", border(&widths, '╞', '═', '╡'))?;
+        self.streaming = Some(Streaming::Table { widths });
+
+        for row in std::mem::take(&mut self.rows) {
+            self.write_streaming_values(&row)?;
+        }
+        Ok(())
+    }
+
+    fn write_streaming_values(&mut self, values: &[Value]) -> std::io::Result<()> {
+        match &mut self.streaming {
+            Some(Streaming::Table { widths }) => {
+                let color = self.output.supports_color();
+                let cells = values
+                    .iter()
+                    .map(|value| {
+                        let text = value_to_string(value.into());
+                        let spec = match value {
+                            Value::Null if color => Some(&self.theme.null),
+                            Value::Integer(_) | Value::Real(_) if color => Some(&self.theme.number),
+                            _ => None,
+                        };
+                        (text, spec)
+                    })
+                    .collect::<Vec<_>>();
+                let cells = cells
+                    .iter()
+                    .map(|(text, spec)| (text.as_str(), *spec))
+                    .collect::<Vec<_>>();
+                write_streaming_row(&mut *self.output, widths, &cells)
+            }
+            Some(Streaming::Lines { num_rows }) => {
+                let values = values
+                    .iter()
+                    .map(|value| value_to_string(value.into()))
+                    .collect::<Vec<_>>();
+                write_line_record(&mut *self.output, &self.column_names, &values, *num_rows)?;
+                *num_rows += 1;
+                Ok(())
+            }
+            None => unreachable!("rows are only written after streaming starts"),
+        }
+    }
+}
+
+/// Get the width of a table with these column widths. Every column has a border and one space of
+/// padding on both sides.
+fn table_width(widths: &[usize]) -> usize {
+    widths.iter().map(|width| width + 3).sum::<usize>() + 1
+}
+
+/// Get a horizontal border of a streamed table.
+fn border(widths: &[usize], left: char, line: char, right: char) -> String {
+    let inner = table_width(widths) - 2;
+    format!("{left}{}{right}", line.to_string().repeat(inner))
+}
+
+/// Shorten text to fit in `width` columns. Only the first line of multi-line text is kept, and
+/// shortened text ends with `…`.
+fn fit_cell(text: &str, width: usize) -> Cow<'_, str> {
+    let first_line = text.split('\n').next().unwrap_or_default();
+    if first_line.len() == text.len() && console::measure_text_width(text) <= width {
+        return Cow::Borrowed(text);
+    }
+    let mut fitted = console::truncate_str(first_line, width.saturating_sub(1), "").into_owned();
+    fitted.push('…');
+    Cow::Owned(fitted)
+}
+
+/// Write a row of a streamed table, with an optional style for each cell.
+fn write_streaming_row(
+    output: &mut dyn WriteColor,
+    widths: &[usize],
+    cells: &[(&str, Option<&ColorSpec>)],
+) -> std::io::Result<()> {
+    write!(output, "│")?;
+    for (&width, &(text, spec)) in widths.iter().zip(cells) {
+        let text = fit_cell(text, width);
+        let padding = width.saturating_sub(console::measure_text_width(&text));
+        write!(output, " ")?;
+        if let Some(spec) = spec {
+            output.set_color(spec)?;
+            write!(output, "{text}")?;
+            output.reset()?;
+        } else {
+            write!(output, "{text}")?;
+        }
+        write!(output, "{:padding$} │", "")?;
+    }
+    writeln!(output)
 }
 
 fn to_hex_string(bytes: &[u8]) -> String {
@@ -129,16 +558,63 @@ fn to_hex_string(bytes: &[u8]) -> String {
     s
 }
 
-fn value_to_cell(value: ValueRef) -> Cell {
+fn value_to_cell(value: ValueRef, theme: &Theme) -> Cell {
     match value {
-        ValueRef::Null => Cell::new("NULL").fg(Color::DarkGrey),
-        ValueRef::Integer(n) => Cell::new(n).fg(Color::Yellow),
-        ValueRef::Real(n) => Cell::new(n).fg(Color::Yellow),
+        ValueRef::Null => styled_cell(Cell::new("NULL"), &theme.null),
+        ValueRef::Integer(n) => styled_cell(Cell::new(n), &theme.number),
+        ValueRef::Real(n) => styled_cell(Cell::new(n), &theme.number),
         ValueRef::Text(text) => Cell::new(String::from_utf8_lossy(text)),
         ValueRef::Blob(blob) => Cell::new(to_hex_string(blob)),
     }
 }
 
+/// Apply a theme style to a table cell.
+fn styled_cell(mut cell: Cell, spec: &ColorSpec) -> Cell {
+    if let Some(fg) = spec.fg() {
+        cell = cell.fg(to_table_color(*fg, spec.intense()));
+    }
+    if let Some(bg) = spec.bg() {
+        cell = cell.bg(to_table_color(*bg, spec.intense()));
+    }
+    for (enabled, attribute) in [
+        (spec.bold(), Attribute::Bold),
+        (spec.italic(), Attribute::Italic),
+        (spec.underline(), Attribute::Underlined),
+    ] {
+        if enabled {
+            cell = cell.add_attribute(attribute);
+        }
+    }
+    cell
+}
+
+/// Convert a termcolor color to the same color in comfy-table, which calls the normal colors
+/// "dark" and the intense ones by their name.
+fn to_table_color(color: termcolor::Color, intense: bool) -> Color {
+    use termcolor::Color as C;
+    match (color, intense) {
+        (C::Black, false) => Color::Black,
+        (C::Black, true) => Color::DarkGrey,
+        (C::Red, false) => Color::DarkRed,
+        (C::Red, true) => Color::Red,
+        (C::Green, false) => Color::DarkGreen,
+        (C::Green, true) => Color::Green,
+        (C::Yellow, false) => Color::DarkYellow,
+        (C::Yellow, true) => Color::Yellow,
+        (C::Blue, false) => Color::DarkBlue,
+        (C::Blue, true) => Color::Blue,
+        (C::Magenta, false) => Color::DarkMagenta,
+        (C::Magenta, true) => Color::Magenta,
+        (C::Cyan, false) => Color::DarkCyan,
+        (C::Cyan, true) => Color::Cyan,
+        (C::White, false) => Color::Grey,
+        (C::White, true) => Color::White,
+        (C::Ansi256(index), _) => Color::AnsiValue(index),
+        (C::Rgb(r, g, b), _) => Color::Rgb { r, g, b },
+        _ => Color::Reset,
+    }
+}
+
 fn value_to_cell_nocolor(value: ValueRef) -> Cell {
     match value {
         ValueRef::Null => Cell::new("NULL"),
@@ -149,34 +625,121 @@ fn value_to_cell_nocolor(value: ValueRef) -> Cell {
     }
 }
 
+/// Format a value as plain text, the same way the table output does without colours.
+fn value_to_string(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(n) => n.to_string(),
+        ValueRef::Real(n) => n.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+        ValueRef::Blob(blob) => to_hex_string(blob),
+    }
+}
+
 impl<'a> OutputRows for TableOutput<'a> {
     fn add_row(&mut self, row: &Row<'_>) -> anyhow::Result<()> {
-        let table_row = (0..self.num_columns)
+        let values = (0..self.column_names.len())
             // We are iterating over column_count() so this should never fail
-            .map(|index| row.get_ref_unwrap(index))
-            .map(if self.output.supports_color() {
-                value_to_cell
-            } else {
-                value_to_cell_nocolor
-            });
-        self.table.add_row(table_row);
+            .map(|index| row.get_ref_unwrap(index).into())
+            .collect::<Vec<Value>>();
+        if self.streaming.is_some() {
+            self.write_streaming_values(&values)?;
+            return Ok(());
+        }
+        self.rows.push(values);
+        if self.rows.len() >= STREAMING_ROWS {
+            self.start_streaming()?;
+        }
         Ok(())
     }
 
     fn finish(&mut self) -> anyhow::Result<()> {
-        self.table
-            .set_content_arrangement(ContentArrangement::Dynamic);
-        // TODO do this in a generic way for all output types
-        if self.table.row(100).is_some() {
-            let mut command = Command::new("less")
-                .stdin(Stdio::piped())
-                .env("LESSCHARSET", "UTF-8")
-                .spawn()?;
-            writeln!(command.stdin.as_mut().unwrap(), "{}", self.table)?;
-            command.wait()?;
-        } else {
-            writeln!(self.output, "{}", self.table)?;
+        match &self.streaming {
+            Some(Streaming::Table { widths }) => {
+                writeln!(self.output, "{}", border(widths, '└', '─', '┘'))?;
+                return Ok(());
+            }
+            Some(Streaming::Lines { .. }) => return Ok(()),
+            None => {}
+        }
+
+        if self.vertical_fallback && self.is_too_wide() {
+            self.write_vertical()?;
+            return Ok(());
+        }
+
+        let mut table = self.build_table();
+        // Only shrink columns to fit in a terminal, never in a file or pipe.
+        if let Some(terminal_width) = self.terminal_width {
+            table.set_content_arrangement(ContentArrangement::Dynamic);
+            table.set_width(terminal_width.try_into().unwrap_or(u16::MAX));
         }
+        writeln!(self.output, "{}", table)?;
+        Ok(())
+    }
+}
+
+pub struct LineOutput<'a> {
+    column_names: Vec<String>,
+    output: &'a mut dyn WriteColor,
+    num_rows: usize,
+}
+
+impl<'a> LineOutput<'a> {
+    pub fn new(statement: &Statement<'_>, output: &'a mut dyn WriteColor) -> Self {
+        Self {
+            column_names: owned_column_names(statement),
+            output,
+            num_rows: 0,
+        }
+    }
+
+    fn write_record(&mut self, values: &[String]) -> std::io::Result<()> {
+        write_line_record(&mut *self.output, &self.column_names, values, self.num_rows)?;
+        self.num_rows += 1;
+        Ok(())
+    }
+}
+
+/// Write a row in `line` format. `row_index` is used to separate rows with a blank line.
+fn write_line_record(
+    output: &mut dyn WriteColor,
+    column_names: &[String],
+    values: &[String],
+    row_index: usize,
+) -> std::io::Result<()> {
+    if row_index > 0 {
+        writeln!(output)?;
+    }
+
+    let name_width = column_names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (name, value) in column_names.iter().zip(values) {
+        let mut lines = value.split('\n');
+        let first = lines.next().unwrap_or_default();
+        writeln!(output, "{name:>name_width$} = {first}")?;
+        // Keep continuation lines of multi-line values aligned with the first line.
+        for line in lines {
+            writeln!(output, "{:name_width$}   {line}", "")?;
+        }
+    }
+    Ok(())
+}
+
+impl<'a> OutputRows for LineOutput<'a> {
+    fn add_row(&mut self, row: &Row<'_>) -> anyhow::Result<()> {
+        let values = (0..self.column_names.len())
+            .map(|index| row.get_ref(index).map(value_to_string))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.write_record(&values)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.output.flush()?;
         Ok(())
     }
 }
@@ -186,13 +749,18 @@ pub struct CsvOutput<'a> {
 }
 
 impl<'a> CsvOutput<'a> {
-    pub fn new(statement: &Statement<'_>, output: &'a mut dyn WriteColor) -> Self {
-        let mut writer = WriterBuilder::new().has_headers(true).from_writer(output);
+    /// Write a CSV file, with a header row of column names if `headers` is set.
+    pub fn new(statement: &Statement<'_>, headers: bool, output: &'a mut dyn WriteColor) -> Self {
+        let mut writer = WriterBuilder::new()
+            .has_headers(headers)
+            .from_writer(output);
 
-        // TODO return result
-        writer
-            .write_byte_record(&ByteRecord::from(statement.column_names()))
-            .unwrap();
+        if headers {
+            // TODO return result
+            writer
+                .write_byte_record(&ByteRecord::from(statement.column_names()))
+                .unwrap();
+        }
 
         Self { writer }
     }
@@ -220,11 +788,262 @@ impl<'a> OutputRows for CsvOutput<'a> {
     }
 }
 
+/// Write a row as a JSON object, keyed by column name.
+///
+/// Integers and reals are written as JSON numbers, NULL as `null`, and blobs as standard base64
+/// strings. Reals that can not be represented in JSON (NaN and infinities) are written as `null`.
+fn write_json_object(
+    output: &mut dyn WriteColor,
+    column_names: &[String],
+    row: &Row<'_>,
+) -> anyhow::Result<()> {
+    use base64::Engine as _;
+    use serde_json::Value;
+
+    output.write_all(b"{")?;
+    for (index, name) in column_names.iter().enumerate() {
+        if index > 0 {
+            output.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut *output, name)?;
+        output.write_all(b":")?;
+        let value = match row.get_ref(index)? {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(n) => Value::from(n),
+            ValueRef::Real(n) => Value::from(n),
+            ValueRef::Text(text) => Value::from(String::from_utf8_lossy(text)),
+            ValueRef::Blob(blob) => {
+                Value::from(base64::engine::general_purpose::STANDARD.encode(blob))
+            }
+        };
+        serde_json::to_writer(&mut *output, &value)?;
+    }
+    output.write_all(b"}")?;
+    Ok(())
+}
+
+fn owned_column_names(statement: &Statement<'_>) -> Vec<String> {
+    statement
+        .column_names()
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+pub struct JsonOutput<'a> {
+    column_names: Vec<String>,
+    output: &'a mut dyn WriteColor,
+    num_rows: usize,
+}
+
+impl<'a> JsonOutput<'a> {
+    pub fn new(statement: &Statement<'_>, output: &'a mut dyn WriteColor) -> Self {
+        Self {
+            column_names: owned_column_names(statement),
+            output,
+            num_rows: 0,
+        }
+    }
+}
+
+impl<'a> OutputRows for JsonOutput<'a> {
+    fn add_row(&mut self, row: &Row<'_>) -> anyhow::Result<()> {
+        let separator: &[u8] = if self.num_rows == 0 { b"[\n" } else { b",\n" };
+        self.output.write_all(separator)?;
+        write_json_object(self.output, &self.column_names, row)?;
+        self.num_rows += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        if self.num_rows == 0 {
+            writeln!(self.output, "[]")?;
+        } else {
+            writeln!(self.output, "\n]")?;
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+pub struct NdjsonOutput<'a> {
+    column_names: Vec<String>,
+    output: &'a mut dyn WriteColor,
+}
+
+impl<'a> NdjsonOutput<'a> {
+    pub fn new(statement: &Statement<'_>, output: &'a mut dyn WriteColor) -> Self {
+        Self {
+            column_names: owned_column_names(statement),
+            output,
+        }
+    }
+}
+
+impl<'a> OutputRows for NdjsonOutput<'a> {
+    fn add_row(&mut self, row: &Row<'_>) -> anyhow::Result<()> {
+        write_json_object(self.output, &self.column_names, row)?;
+        writeln!(self.output)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// Escape a value for use in a Markdown table cell. Pipes are escaped and line breaks are
+/// replaced by `<br>`, because a cell can not span multiple lines.
+fn escape_markdown_cell(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '|' => escaped.push_str("\\|"),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("<br>"),
+            '\r' => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub struct MarkdownOutput<'a> {
+    column_names: Vec<String>,
+    output: &'a mut dyn WriteColor,
+    /// Whether to write the header row. Without it, the output is only the rows of a table.
+    headers: bool,
+    wrote_header: bool,
+}
+
+impl<'a> MarkdownOutput<'a> {
+    pub fn new(statement: &Statement<'_>, headers: bool, output: &'a mut dyn WriteColor) -> Self {
+        Self {
+            column_names: owned_column_names(statement),
+            output,
+            headers,
+            wrote_header: false,
+        }
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        if self.wrote_header || !self.headers {
+            return Ok(());
+        }
+        self.wrote_header = true;
+
+        let mut header = String::from("|");
+        let mut separator = String::from("|");
+        for name in &self.column_names {
+            _ = write!(header, " {} |", escape_markdown_cell(name));
+            separator.push_str(" --- |");
+        }
+        writeln!(self.output, "{}", header)?;
+        writeln!(self.output, "{}", separator)
+    }
+}
+
+impl<'a> OutputRows for MarkdownOutput<'a> {
+    fn add_row(&mut self, row: &Row<'_>) -> anyhow::Result<()> {
+        self.write_header()?;
+        let mut line = String::from("|");
+        for index in 0..self.column_names.len() {
+            let value = value_to_string(row.get_ref(index)?);
+            _ = write!(line, " {} |", escape_markdown_cell(&value));
+        }
+        writeln!(self.output, "{}", line)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.write_header()?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub struct HtmlOutput<'a> {
+    column_names: Vec<String>,
+    output: &'a mut dyn WriteColor,
+    /// Whether to write a `<thead>` with the column names.
+    headers: bool,
+    wrote_header: bool,
+}
+
+impl<'a> HtmlOutput<'a> {
+    pub fn new(statement: &Statement<'_>, headers: bool, output: &'a mut dyn WriteColor) -> Self {
+        Self {
+            column_names: owned_column_names(statement),
+            output,
+            headers,
+            wrote_header: false,
+        }
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        if self.wrote_header {
+            return Ok(());
+        }
+        self.wrote_header = true;
+
+        writeln!(self.output, "<table>")?;
+        if self.headers {
+            let mut header = String::from("<tr>");
+            for name in &self.column_names {
+                _ = write!(header, "<th>{}</th>", escape_html(name));
+            }
+            header.push_str("</tr>");
+            writeln!(self.output, "<thead>")?;
+            writeln!(self.output, "{}", header)?;
+            writeln!(self.output, "</thead>")?;
+        }
+        writeln!(self.output, "<tbody>")
+    }
+}
+
+impl<'a> OutputRows for HtmlOutput<'a> {
+    fn add_row(&mut self, row: &Row<'_>) -> anyhow::Result<()> {
+        self.write_header()?;
+        let mut line = String::from("<tr>");
+        for index in 0..self.column_names.len() {
+            let value = value_to_string(row.get_ref(index)?);
+            _ = write!(line, "<td>{}</td>", escape_html(&value));
+        }
+        line.push_str("</tr>");
+        writeln!(self.output, "{}", line)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.write_header()?;
+        writeln!(self.output, "</tbody>")?;
+        writeln!(self.output, "</table>")?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
 pub struct SqlOutput<'a> {
     table_name: String,
+    column_names: Vec<String>,
     highlighter: &'a SqlHighlighter,
     output: &'a mut dyn WriteColor,
-    num_columns: usize,
 }
 
 impl<'a> SqlOutput<'a> {
@@ -233,13 +1052,11 @@ impl<'a> SqlOutput<'a> {
         highlighter: &'a SqlHighlighter,
         output: &'a mut dyn WriteColor,
     ) -> Self {
-        let num_columns = statement.column_count();
-
         Self {
             table_name: "tbl".to_string(),
+            column_names: owned_column_names(statement),
             highlighter,
             output,
-            num_columns,
         }
     }
 
@@ -256,31 +1073,21 @@ impl<'a> SqlOutput<'a> {
     }
 }
 
-fn to_sql_hex_string(bytes: &[u8], output: &mut impl std::fmt::Write) {
-    let _ = write!(output, "X'");
-    for b in bytes {
-        _ = write!(output, "{b:02x}");
-    }
-    let _ = write!(output, "'");
-}
-
 impl<'a> OutputRows for SqlOutput<'a> {
     fn add_row(&mut self, row: &Row<'_>) -> anyhow::Result<()> {
-        let mut sql = format!("INSERT INTO {} VALUES(", &self.table_name);
-        for index in 0..self.num_columns {
-            use std::fmt::Write;
+        let mut sql = format!("INSERT INTO {}(", quote_identifier(&self.table_name));
+        for (index, name) in self.column_names.iter().enumerate() {
             if index > 0 {
-                sql.push_str(", ");
+                sql.push(',');
             }
-            match row.get_ref(index)? {
-                ValueRef::Null => sql.push_str("NULL"),
-                ValueRef::Integer(n) => write!(&mut sql, "{}", n).unwrap(),
-                ValueRef::Real(n) => write!(&mut sql, "{}", n).unwrap(),
-                ValueRef::Text(text) => {
-                    write!(&mut sql, "'{}'", std::str::from_utf8(text).unwrap()).unwrap()
-                }
-                ValueRef::Blob(blob) => to_sql_hex_string(blob, &mut sql),
+            sql.push_str(&quote_identifier(name));
+        }
+        sql.push_str(") VALUES(");
+        for index in 0..self.column_names.len() {
+            if index > 0 {
+                sql.push_str(", ");
             }
+            write_sql_literal(row.get_ref(index)?, &mut sql);
         }
         sql.push_str(");");
         self.println(&sql)?;
@@ -290,3 +1097,161 @@ impl<'a> OutputRows for SqlOutput<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+    use termcolor::NoColor;
+
+    fn render(mode: OutputMode, sql: &str) -> String {
+        render_with_headers(mode, sql, true)
+    }
+
+    fn render_with_headers(mode: OutputMode, sql: &str, headers: bool) -> String {
+        let conn = Connection::open_in_memory().unwrap();
+        let highlighter = SqlHighlighter::new();
+        let mut stmt = conn.prepare(sql).unwrap();
+        let mut output = NoColor::new(vec![]);
+        {
+            let mut output_rows = mode.output_rows(&stmt, &highlighter, None, headers, &mut output);
+            let mut query = stmt.query([]).unwrap();
+            while let Some(row) = query.next().unwrap() {
+                output_rows.add_row(row).unwrap();
+            }
+            output_rows.finish().unwrap();
+        }
+        String::from_utf8(output.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn json_output() {
+        assert_eq!(
+            render(
+                OutputMode::Json,
+                "SELECT 1 AS a, 2.5 AS b, NULL AS c, 'x\"y' AS d, X'0102ff' AS e \
+                 UNION ALL SELECT 2, 0.5, NULL, '', X''"
+            ),
+            "[\n\
+             {\"a\":1,\"b\":2.5,\"c\":null,\"d\":\"x\\\"y\",\"e\":\"AQL/\"},\n\
+             {\"a\":2,\"b\":0.5,\"c\":null,\"d\":\"\",\"e\":\"\"}\n\
+             ]\n",
+        );
+        assert_eq!(render(OutputMode::Json, "SELECT 1 WHERE 0"), "[]\n");
+    }
+
+    #[test]
+    fn output_without_headers() {
+        let sql = "SELECT 1 AS a, 'x' AS b";
+        assert_eq!(render_with_headers(OutputMode::Csv, sql, false), "1,x\n");
+        assert_eq!(
+            render_with_headers(OutputMode::Markdown, sql, false),
+            "| 1 | x |\n"
+        );
+        assert_eq!(
+            render_with_headers(OutputMode::Html, sql, false),
+            "<table>\n<tbody>\n<tr><td>1</td><td>x</td></tr>\n</tbody>\n</table>\n"
+        );
+        assert_eq!(
+            render_with_headers(OutputMode::Csv, sql, true),
+            "a,b\n1,x\n"
+        );
+    }
+
+    #[test]
+    fn markdown_output() {
+        assert_eq!(
+            render(
+                OutputMode::Markdown,
+                "SELECT 1 AS a, 'x|y' AS \"b|c\", NULL AS d UNION ALL SELECT 2, 'line\nbreak', X'ff'"
+            ),
+            "| a | b\\|c | d |\n\
+             | --- | --- | --- |\n\
+             | 1 | x\\|y | NULL |\n\
+             | 2 | line<br>break | ff |\n",
+        );
+    }
+
+    #[test]
+    fn html_output() {
+        assert_eq!(
+            render(OutputMode::Html, "SELECT '<b>&</b>' AS \"a<\""),
+            "<table>\n\
+             <thead>\n\
+             <tr><th>a&lt;</th></tr>\n\
+             </thead>\n\
+             <tbody>\n\
+             <tr><td>&lt;b&gt;&amp;&lt;/b&gt;</td></tr>\n\
+             </tbody>\n\
+             </table>\n",
+        );
+    }
+
+    #[test]
+    fn line_output() {
+        assert_eq!(
+            render(
+                OutputMode::Line,
+                "SELECT 1 AS id, 'a\nb' AS description UNION ALL SELECT 2, NULL"
+            ),
+            "         id = 1\n\
+             description = a\n\
+             \x20             b\n\
+             \n\
+             \x20        id = 2\n\
+             description = NULL\n",
+        );
+    }
+
+    #[test]
+    fn auto_output_without_terminal() {
+        // Files and pipes have no width, so a wide table is not switched to `line` output.
+        let sql = format!("SELECT 1 AS id, '{}' AS wide", "x".repeat(500));
+        let output = render(OutputMode::Auto, &sql);
+        assert!(output.starts_with('┌'));
+        assert!(output.contains(&"x".repeat(500)));
+        assert_eq!(output, render(OutputMode::Table, &sql));
+    }
+
+    #[test]
+    fn streaming_table_output() {
+        let output = render(
+            OutputMode::Table,
+            &format!(
+                "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n LIMIT {}) \
+                 SELECT i, CASE WHEN i > {} THEN 'a longer value' ELSE 'value' END AS v FROM n",
+                STREAMING_ROWS + 1,
+                STREAMING_ROWS
+            ),
+        );
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), STREAMING_ROWS + 5);
+        assert_eq!(lines[0], "┌──────────────┐");
+        assert_eq!(lines[1], "│ i    │ v     │");
+        assert_eq!(lines[2], "╞══════════════╡");
+        assert_eq!(lines[3], "│ 1    │ value │");
+        // Column widths are based on the rows before streaming started.
+        assert_eq!(lines[STREAMING_ROWS + 3], "│ 1001 │ a lo… │");
+        assert_eq!(lines[STREAMING_ROWS + 4], "└──────────────┘");
+    }
+
+    #[test]
+    fn sql_output() {
+        assert_eq!(
+            render(
+                OutputMode::Sql,
+                "SELECT 1 AS id, 'it''s' AS \"na\"\"me\", 1.0 AS r, NULL AS n, X'00ff' AS b"
+            ),
+            "INSERT INTO \"tbl\"(\"id\",\"na\"\"me\",\"r\",\"n\",\"b\") \
+             VALUES(1, 'it''s', 1.0, NULL, X'00ff');\n",
+        );
+    }
+
+    #[test]
+    fn ndjson_output() {
+        assert_eq!(
+            render(OutputMode::Ndjson, "SELECT 1 AS a UNION ALL SELECT 2"),
+            "{\"a\":1}\n{\"a\":2}\n",
+        );
+    }
+}
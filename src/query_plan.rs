@@ -0,0 +1,103 @@
+//! Print the output of EXPLAIN QUERY PLAN as a tree.
+
+use crate::theme::Theme;
+use rusqlite::Statement;
+use termcolor::WriteColor;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    id: i64,
+    parent: i64,
+    detail: String,
+}
+
+/// Run an EXPLAIN QUERY PLAN statement and collect its steps.
+pub fn query_plan(stmt: &mut Statement<'_>) -> rusqlite::Result<Vec<Step>> {
+    let steps = stmt.query_map([], |row| {
+        Ok(Step {
+            id: row.get("id")?,
+            parent: row.get("parent")?,
+            detail: row.get("detail")?,
+        })
+    })?;
+    steps.collect()
+}
+
+/// Print the steps of a query plan as a tree, like the official SQLite CLI does. Full table scans
+/// are highlighted.
+pub fn write_query_plan(
+    steps: &[Step],
+    theme: &Theme,
+    output: &mut dyn WriteColor,
+) -> std::io::Result<()> {
+    writeln!(output, "QUERY PLAN")?;
+    write_children(steps, 0, "", theme, output)
+}
+
+fn write_children(
+    steps: &[Step],
+    parent: i64,
+    prefix: &str,
+    theme: &Theme,
+    output: &mut dyn WriteColor,
+) -> std::io::Result<()> {
+    let children = steps
+        .iter()
+        .filter(|step| step.parent == parent)
+        .collect::<Vec<_>>();
+    for (index, step) in children.iter().enumerate() {
+        let is_last = index == children.len() - 1;
+        write!(output, "{prefix}{}", if is_last { "└── " } else { "├── " })?;
+        if step.detail.starts_with("SCAN ") {
+            output.set_color(&theme.scan)?;
+            write!(output, "{}", step.detail)?;
+            output.reset()?;
+            writeln!(output)?;
+        } else {
+            writeln!(output, "{}", step.detail)?;
+        }
+
+        let child_prefix = format!("{prefix}{}", if is_last { "    " } else { "│   " });
+        write_children(steps, step.id, &child_prefix, theme, output)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+    use termcolor::NoColor;
+
+    #[test]
+    fn query_plan_tree() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+            CREATE TABLE posts (user_id INTEGER, title TEXT);",
+        )
+        .unwrap();
+        let mut stmt = conn
+            .prepare(
+                "EXPLAIN QUERY PLAN SELECT * FROM posts
+                WHERE user_id IN (SELECT id FROM users WHERE name = 'a')
+                ORDER BY title",
+            )
+            .unwrap();
+        let steps = query_plan(&mut stmt).unwrap();
+        assert!(steps.iter().any(|step| step.parent != 0));
+
+        let mut output = NoColor::new(vec![]);
+        write_query_plan(&steps, &Theme::default(), &mut output).unwrap();
+        let output = String::from_utf8(output.into_inner()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "QUERY PLAN");
+        assert_eq!(lines.len(), steps.len() + 1);
+        assert!(lines[1].starts_with("├── SCAN posts"));
+        assert!(lines.iter().any(|line| line.starts_with("│   └── ")));
+        assert!(lines
+            .last()
+            .unwrap()
+            .starts_with("└── USE TEMP B-TREE FOR ORDER BY"));
+    }
+}
@@ -1,3 +1,6 @@
+use rusqlite::types::{Value, ValueRef};
+use std::ffi::CString;
+use std::fmt::Write as _;
 use tree_sitter::{Node, Parser, QueryCursor, TextProvider, Tree};
 
 fn text_provider(input: &str) -> impl TextProvider<'_> {
@@ -31,6 +34,171 @@ impl<'a> ParsedSql<'a> {
         }
         nodes
     }
+
+    /// Get the byte ranges of syntax errors: tokens that could not be parsed, and the tokens
+    /// before which something is missing.
+    pub fn error_ranges(&self) -> Vec<std::ops::Range<usize>> {
+        fn collect(node: Node<'_>, source: &str, ranges: &mut Vec<std::ops::Range<usize>>) {
+            if node.is_missing() {
+                // Missing nodes are empty, so point at the next character instead.
+                let start = node.start_byte();
+                if let Some(c) = source[start..].chars().next() {
+                    ranges.push(start..start + c.len_utf8());
+                }
+            } else if node.is_error() {
+                ranges.push(node.byte_range());
+            } else if node.has_error() {
+                let mut cursor = node.walk();
+                for child in node.children(&mut cursor) {
+                    collect(child, source, ranges);
+                }
+            }
+        }
+
+        let mut ranges = vec![];
+        collect(self.tree.root_node(), self.source, &mut ranges);
+        ranges
+    }
+}
+
+/// Find the byte ranges of strings, quoted names and comments, including their quotes. Ranges
+/// that are not closed reach the end of the input, and line comments end before the newline.
+pub fn quoted_ranges(sql: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = vec![];
+    let mut chars = sql.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let end = match c {
+            '\'' | '"' | '`' => c,
+            '[' => ']',
+            '-' if chars.peek().is_some_and(|&(_, next)| next == '-') => '\n',
+            '/' if chars.peek().is_some_and(|&(_, next)| next == '*') => {
+                chars.next();
+                let mut previous = ' ';
+                let end = chars
+                    .by_ref()
+                    .find(|&(_, c)| std::mem::replace(&mut previous, c) == '*' && c == '/')
+                    .map_or(sql.len(), |(index, _)| index + 1);
+                ranges.push(start..end);
+                continue;
+            }
+            _ => continue,
+        };
+        let end = match chars.by_ref().find(|&(_, c)| c == end) {
+            Some((index, '\n')) => index,
+            Some((index, c)) => index + c.len_utf8(),
+            None => sql.len(),
+        };
+        ranges.push(start..end);
+    }
+    ranges
+}
+
+/// Check if the input ends inside a string, quoted identifier, or block comment. Line comments
+/// end at the end of the input.
+fn ends_inside_quote(sql: &str) -> bool {
+    quoted_ranges(sql).last().is_some_and(|range| {
+        let quoted = &sql[range.clone()];
+        let closed = match quoted.as_bytes()[0] {
+            b'-' => true,
+            b'/' => quoted.len() >= 4 && quoted.ends_with("*/"),
+            b'[' => quoted.len() >= 2 && quoted.ends_with(']'),
+            quote => quoted.len() >= 2 && quoted.as_bytes()[quoted.len() - 1] == quote,
+        };
+        range.end == sql.len() && !closed
+    })
+}
+
+/// Find the bracket that matches the one at `pos`. Brackets in strings, quoted names and comments
+/// are skipped.
+pub fn matching_bracket(sql: &str, pos: usize) -> Option<usize> {
+    let quoted = quoted_ranges(sql);
+    let brackets = sql
+        .char_indices()
+        .filter(|&(index, c)| {
+            matches!(c, '(' | ')') && !quoted.iter().any(|range| range.contains(&index))
+        })
+        .collect::<Vec<_>>();
+
+    let position = brackets.iter().position(|&(index, _)| index == pos)?;
+    let mut depth = 0;
+    if brackets[position].1 == '(' {
+        for &(index, c) in &brackets[position..] {
+            depth += if c == '(' { 1 } else { -1 };
+            if depth == 0 {
+                return Some(index);
+            }
+        }
+    } else {
+        for &(index, c) in brackets[..=position].iter().rev() {
+            depth += if c == ')' { 1 } else { -1 };
+            if depth == 0 {
+                return Some(index);
+            }
+        }
+    }
+    None
+}
+
+/// Check if the input needs more lines before it can be executed, like the official SQLite CLI
+/// does. Dot commands are always complete.
+pub fn is_incomplete(sql: &str) -> bool {
+    let sql = sql.trim_start();
+    if sql.is_empty() || sql.starts_with('.') {
+        return false;
+    }
+    if ends_inside_quote(sql) || has_open_bracket(sql) {
+        return true;
+    }
+    // SQLite's own check knows that the `;` in a trigger body does not end the statement.
+    let Ok(sql) = CString::new(sql) else {
+        return false;
+    };
+    unsafe { rusqlite::ffi::sqlite3_complete(sql.as_ptr()) == 0 }
+}
+
+/// Check if the input has a bracket that is not closed. Brackets in strings, quoted names and
+/// comments are skipped.
+fn has_open_bracket(sql: &str) -> bool {
+    let quoted = quoted_ranges(sql);
+    let mut depth = 0usize;
+    for (index, byte) in sql.bytes().enumerate() {
+        if quoted.iter().any(|range| range.contains(&index)) {
+            continue;
+        }
+        match byte {
+            b'(' => depth += 1,
+            b')' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    depth > 0
+}
+
+/// How a statement is prefixed with `EXPLAIN`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainKind {
+    None,
+    /// `EXPLAIN ...`, which returns the bytecode of the statement.
+    Explain,
+    /// `EXPLAIN QUERY PLAN ...`.
+    QueryPlan,
+}
+
+/// Check if a statement starts with `EXPLAIN` or `EXPLAIN QUERY PLAN`.
+pub fn explain_kind(stmt_node: Node<'_>, source: &str) -> ExplainKind {
+    let mut cursor = stmt_node.walk();
+    let keywords = stmt_node
+        .children(&mut cursor)
+        .take(3)
+        .map(|child| source[child.byte_range()].to_ascii_uppercase())
+        .collect::<Vec<_>>();
+    match keywords.as_slice() {
+        [explain, query, plan] if explain == "EXPLAIN" && query == "QUERY" && plan == "PLAN" => {
+            ExplainKind::QueryPlan
+        }
+        [explain, ..] if explain == "EXPLAIN" => ExplainKind::Explain,
+        _ => ExplainKind::None,
+    }
 }
 
 pub fn parse_sql(sql: &str) -> anyhow::Result<ParsedSql<'_>> {
@@ -40,10 +208,208 @@ pub fn parse_sql(sql: &str) -> anyhow::Result<ParsedSql<'_>> {
     Ok(ParsedSql { tree, source: sql })
 }
 
+/// Quote an SQL identifier, such as a table or column name.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Write a value as an SQL literal that evaluates to the same value, with the same type.
+pub fn write_sql_literal(value: ValueRef<'_>, sql: &mut String) {
+    match value {
+        ValueRef::Null => sql.push_str("NULL"),
+        ValueRef::Integer(n) => _ = write!(sql, "{}", n),
+        // SQLite stores NaN as NULL, so it does not need to be handled.
+        ValueRef::Real(n) if n.is_infinite() => sql.push_str(if n.is_sign_positive() {
+            "9e999"
+        } else {
+            "-9e999"
+        }),
+        // The Debug format always includes a decimal point or an exponent, so the value stays a
+        // REAL, and it prints the shortest representation that parses back to the same value.
+        ValueRef::Real(n) => _ = write!(sql, "{:?}", n),
+        ValueRef::Text(text) => match std::str::from_utf8(text) {
+            Ok(text) if !text.contains('\0') => {
+                sql.push('\'');
+                sql.push_str(&text.replace('\'', "''"));
+                sql.push('\'');
+            }
+            // Not representable as a string literal.
+            _ => {
+                sql.push_str("CAST(");
+                write_sql_blob(text, sql);
+                sql.push_str(" AS TEXT)");
+            }
+        },
+        ValueRef::Blob(blob) => write_sql_blob(blob, sql),
+    }
+}
+
+/// Parse a value typed by the user as an SQL literal: `NULL`, a number, a `'string'`, or a
+/// `X'blob'`. Anything else is used as text.
+pub fn parse_literal(input: &str) -> Value {
+    let trimmed = input.trim();
+    if trimmed.eq_ignore_ascii_case("NULL") {
+        return Value::Null;
+    }
+    // Rust also parses words like `inf` and `NaN` as floats, which should stay text here.
+    if trimmed.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.')) {
+        if let Ok(n) = trimmed.parse::<i64>() {
+            return Value::Integer(n);
+        }
+        if let Ok(n) = trimmed.parse::<f64>() {
+            return Value::Real(n);
+        }
+    }
+    if let Some(quoted) = trimmed
+        .strip_prefix('\'')
+        .and_then(|rest| rest.strip_suffix('\''))
+    {
+        // Quotes inside the string must be doubled.
+        if quoted.replace("''", "").find('\'').is_none() {
+            return Value::Text(quoted.replace("''", "'"));
+        }
+    }
+    if let Some(hex) = trimmed
+        .strip_prefix(['X', 'x'])
+        .and_then(|rest| rest.strip_prefix('\''))
+        .and_then(|rest| rest.strip_suffix('\''))
+    {
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|index| {
+                hex.get(index..index + 2)
+                    .and_then(|b| u8::from_str_radix(b, 16).ok())
+            })
+            .collect::<Option<Vec<_>>>();
+        if let Some(bytes) = bytes.filter(|_| hex.len() % 2 == 0) {
+            return Value::Blob(bytes);
+        }
+    }
+    Value::Text(input.to_string())
+}
+
+fn write_sql_blob(bytes: &[u8], sql: &mut String) {
+    sql.push_str("X'");
+    for b in bytes {
+        _ = write!(sql, "{b:02x}");
+    }
+    sql.push('\'');
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn literal(value: ValueRef<'_>) -> String {
+        let mut sql = String::new();
+        write_sql_literal(value, &mut sql);
+        sql
+    }
+
+    #[test]
+    fn sql_literals() {
+        assert_eq!(literal(ValueRef::Null), "NULL");
+        assert_eq!(literal(ValueRef::Integer(-3)), "-3");
+        assert_eq!(literal(ValueRef::Real(1.0)), "1.0");
+        assert_eq!(literal(ValueRef::Real(0.1)), "0.1");
+        assert_eq!(literal(ValueRef::Real(1e300)), "1e300");
+        assert_eq!(literal(ValueRef::Real(f64::NEG_INFINITY)), "-9e999");
+        assert_eq!(literal(ValueRef::Text(b"it's")), "'it''s'");
+        assert_eq!(literal(ValueRef::Text(b"\xff")), "CAST(X'ff' AS TEXT)");
+        assert_eq!(literal(ValueRef::Blob(b"\x01\xab")), "X'01ab'");
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(parse_literal("NULL"), Value::Null);
+        assert_eq!(parse_literal("null"), Value::Null);
+        assert_eq!(parse_literal("42"), Value::Integer(42));
+        assert_eq!(parse_literal(" -7 "), Value::Integer(-7));
+        assert_eq!(parse_literal("3.5"), Value::Real(3.5));
+        assert_eq!(parse_literal("1e3"), Value::Real(1000.0));
+        assert_eq!(parse_literal("'42'"), Value::Text("42".into()));
+        assert_eq!(parse_literal("'it''s'"), Value::Text("it's".into()));
+        assert_eq!(parse_literal("X'01aB'"), Value::Blob(vec![0x01, 0xab]));
+        assert_eq!(
+            parse_literal("hello world"),
+            Value::Text("hello world".into())
+        );
+        assert_eq!(parse_literal("inf"), Value::Text("inf".into()));
+        assert_eq!(parse_literal("'it's'"), Value::Text("'it's'".into()));
+        assert_eq!(parse_literal("X'abc'"), Value::Text("X'abc'".into()));
+        assert_eq!(parse_literal(""), Value::Text("".into()));
+    }
+
+    #[test]
+    fn inside_quote() {
+        assert!(!ends_inside_quote("SELECT 'it''s', \"a\", [b], `c`;"));
+        assert!(!ends_inside_quote("SELECT 1; -- it's a comment"));
+        assert!(!ends_inside_quote("SELECT /* it's */ 1;"));
+        assert!(ends_inside_quote("SELECT 'abc;"));
+        assert!(ends_inside_quote("SELECT 'it''s;"));
+        assert!(ends_inside_quote("SELECT \"abc;"));
+        assert!(ends_inside_quote("SELECT 1; /* comment"));
+        assert!(ends_inside_quote("SELECT 1; /*/"));
+        assert!(ends_inside_quote("SELECT [a"));
+        assert!(ends_inside_quote("SELECT '"));
+    }
+
+    #[test]
+    fn find_quoted_ranges() {
+        let quoted = |sql| {
+            quoted_ranges(sql)
+                .into_iter()
+                .map(|range| &sql[range])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            quoted("SELECT 'it''s', \"a\", [b] -- c\n/* d */ 'e"),
+            ["'it'", "'s'", "\"a\"", "[b]", "-- c", "/* d */", "'e"]
+        );
+        assert_eq!(quoted("SELECT 1 /* a */ /* b"), ["/* a */", "/* b"]);
+        assert!(quoted("SELECT a - b / c").is_empty());
+    }
+
+    #[test]
+    fn matching_brackets() {
+        let sql = "SELECT (1 + (2)) AS \"(\", ')' -- (\n, count(*)";
+        assert_eq!(matching_bracket(sql, 7), Some(15));
+        assert_eq!(matching_bracket(sql, 15), Some(7));
+        assert_eq!(matching_bracket(sql, 12), Some(14));
+        let count = sql.rfind('(').unwrap();
+        assert_eq!(matching_bracket(sql, count), Some(sql.len() - 1));
+        // Not a bracket, or inside a quoted name.
+        assert_eq!(matching_bracket(sql, 0), None);
+        assert_eq!(matching_bracket(sql, 21), None);
+        assert_eq!(matching_bracket("SELECT (1", 7), None);
+    }
+
+    #[test]
+    fn incomplete_statements() {
+        assert!(!is_incomplete(""));
+        assert!(!is_incomplete("  \n"));
+        assert!(!is_incomplete(".tables"));
+        assert!(!is_incomplete("SELECT 1;"));
+        assert!(!is_incomplete("SELECT 1;\n"));
+        assert!(!is_incomplete("SELECT 1; SELECT 2;"));
+        assert!(!is_incomplete("SELECT 1; -- done"));
+        assert!(is_incomplete("SELECT 1"));
+        assert!(is_incomplete("SELECT *\nFROM"));
+        assert!(is_incomplete("SELECT 1; SELECT 2"));
+        assert!(is_incomplete("SELECT (1"));
+        assert!(is_incomplete("SELECT 'abc;"));
+        assert!(!is_incomplete("SELECT ')';"));
+        // The `;` inside a trigger body does not end the statement.
+        let trigger =
+            "CREATE TRIGGER log AFTER INSERT ON users BEGIN\n  INSERT INTO log VALUES (1);\n";
+        assert!(is_incomplete(trigger));
+        assert!(is_incomplete(&format!(
+            "{trigger}  UPDATE log SET id = 2;\n"
+        )));
+        assert!(!is_incomplete(&format!("{trigger}END;\n")));
+    }
+
     #[test]
     fn get_statements() {
         let tree = parse_sql(
@@ -68,4 +434,24 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn explain_statements() {
+        let sql = "EXPLAIN SELECT 1; EXPLAIN QUERY PLAN SELECT 1; explain query plan select 1; \
+                   explain select 1; SELECT 1";
+        let tree = parse_sql(sql).unwrap();
+        assert_eq!(
+            tree.statements()
+                .into_iter()
+                .map(|node| explain_kind(node, sql))
+                .collect::<Vec<_>>(),
+            vec![
+                ExplainKind::Explain,
+                ExplainKind::QueryPlan,
+                ExplainKind::QueryPlan,
+                ExplainKind::Explain,
+                ExplainKind::None,
+            ]
+        );
+    }
 }
@@ -0,0 +1,353 @@
+//! Colors for highlighted SQL, query results and the input line.
+
+use anyhow::{bail, Context as _};
+use directories::ProjectDirs;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use termcolor::{Color, ColorSpec};
+
+/// Themes that are always available. Others are loaded from the themes directory.
+const BUILT_IN_THEMES: [&str; 2] = ["dark", "light"];
+
+/// Names of the styles in a theme, as used in theme files.
+const STYLE_NAMES: [&str; 13] = [
+    "keyword",
+    "number",
+    "string",
+    "constant",
+    "comment",
+    "operator",
+    "punctuation",
+    "parameter",
+    "null",
+    "error",
+    "bracket",
+    "hint",
+    "scan",
+];
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    pub keyword: ColorSpec,
+    /// Numbers in SQL and in query results.
+    pub number: ColorSpec,
+    pub string: ColorSpec,
+    /// Constants like NULL and TRUE in SQL.
+    pub constant: ColorSpec,
+    pub comment: ColorSpec,
+    pub operator: ColorSpec,
+    pub punctuation: ColorSpec,
+    /// Bind parameters like `:name` or `?1`.
+    pub parameter: ColorSpec,
+    /// NULL in query results.
+    pub null: ColorSpec,
+    /// Syntax errors in the input line.
+    pub error: ColorSpec,
+    /// The bracket under the cursor, and the one that matches it.
+    pub bracket: ColorSpec,
+    /// Completion hints after the cursor.
+    pub hint: ColorSpec,
+    /// Full table scans in query plans.
+    pub scan: ColorSpec,
+}
+
+fn style(fg: Option<Color>, bold: bool) -> ColorSpec {
+    let mut spec = ColorSpec::new();
+    spec.set_fg(fg).set_bold(bold);
+    spec
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            name: "dark".to_string(),
+            keyword: style(Some(Color::Blue), true),
+            number: style(Some(Color::Yellow), true),
+            string: style(Some(Color::Magenta), true),
+            constant: style(Some(Color::Cyan), true),
+            comment: style(Some(Color::Green), true),
+            operator: ColorSpec::new(),
+            punctuation: ColorSpec::new(),
+            parameter: style(Some(Color::Magenta), true),
+            null: style(Some(Color::Ansi256(8)), false),
+            error: style(Some(Color::Red), false).set_underline(true).clone(),
+            bracket: style(Some(Color::Cyan), true),
+            hint: style(Some(Color::Ansi256(8)), true),
+            scan: style(Some(Color::Yellow), true),
+        }
+    }
+
+    /// Darker colors that are readable on a light background.
+    pub fn light() -> Self {
+        Self {
+            name: "light".to_string(),
+            keyword: style(Some(Color::Ansi256(25)), true),
+            number: style(Some(Color::Ansi256(130)), false),
+            string: style(Some(Color::Ansi256(127)), false),
+            constant: style(Some(Color::Ansi256(30)), true),
+            comment: style(Some(Color::Ansi256(28)), false),
+            operator: ColorSpec::new(),
+            punctuation: ColorSpec::new(),
+            parameter: style(Some(Color::Ansi256(127)), true),
+            null: style(Some(Color::Ansi256(245)), false),
+            error: style(Some(Color::Ansi256(160)), false)
+                .set_underline(true)
+                .clone(),
+            bracket: style(Some(Color::Ansi256(30)), true),
+            hint: style(Some(Color::Ansi256(247)), false),
+            scan: style(Some(Color::Ansi256(130)), true),
+        }
+    }
+
+    /// Load a built-in theme, or a theme file called `{name}.toml` from the themes directory.
+    /// True colors are replaced by the closest of 256 colors if the terminal does not support them.
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        let theme = match name {
+            "dark" => Self::dark(),
+            "light" => Self::light(),
+            _ => {
+                let path = themes_dir()
+                    .context("could not find the config directory")?
+                    .join(format!("{name}.toml"));
+                let text = std::fs::read_to_string(&path)
+                    .with_context(|| format!("could not read {}", path.display()))?;
+                Self::parse(name, &text)
+                    .with_context(|| format!("invalid theme {}", path.display()))?
+            }
+        };
+        if supports_true_color() {
+            Ok(theme)
+        } else {
+            Ok(theme.with_256_colors())
+        }
+    }
+
+    /// Parse a theme file. Styles that are not in the file come from the `base` theme.
+    fn parse(name: &str, text: &str) -> anyhow::Result<Self> {
+        let file: ThemeFile = toml::from_str(text)?;
+        let mut theme = match file.base.as_deref() {
+            None | Some("dark") => Self::dark(),
+            Some("light") => Self::light(),
+            Some(base) => bail!("unknown base theme `{base}`, expected `dark` or `light`"),
+        };
+        theme.name = name.to_string();
+        for (style_name, config) in file.styles {
+            let Some(spec) = theme.style_mut(&style_name) else {
+                bail!(
+                    "unknown style `{style_name}`, expected one of: {}",
+                    STYLE_NAMES.join(", ")
+                );
+            };
+            *spec = config
+                .to_color_spec()
+                .with_context(|| format!("invalid style `{style_name}`"))?;
+        }
+        Ok(theme)
+    }
+
+    fn style_mut(&mut self, name: &str) -> Option<&mut ColorSpec> {
+        Some(match name {
+            "keyword" => &mut self.keyword,
+            "number" => &mut self.number,
+            "string" => &mut self.string,
+            "constant" => &mut self.constant,
+            "comment" => &mut self.comment,
+            "operator" => &mut self.operator,
+            "punctuation" => &mut self.punctuation,
+            "parameter" => &mut self.parameter,
+            "null" => &mut self.null,
+            "error" => &mut self.error,
+            "bracket" => &mut self.bracket,
+            "hint" => &mut self.hint,
+            "scan" => &mut self.scan,
+            _ => return None,
+        })
+    }
+
+    fn with_256_colors(mut self) -> Self {
+        for name in STYLE_NAMES {
+            let spec = self.style_mut(name).unwrap();
+            let fg = spec.fg().copied().map(to_256_color);
+            let bg = spec.bg().copied().map(to_256_color);
+            spec.set_fg(fg).set_bg(bg);
+        }
+        self
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// Get the names of the built-in themes and the theme files.
+pub fn theme_names() -> Vec<String> {
+    let mut names = BUILT_IN_THEMES.map(String::from).to_vec();
+    if let Some(entries) = themes_dir().and_then(|dir| std::fs::read_dir(dir).ok()) {
+        let mut files = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != "toml" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .filter(|name| !BUILT_IN_THEMES.contains(&name.as_str()))
+            .collect::<Vec<_>>();
+        files.sort();
+        names.extend(files);
+    }
+    names
+}
+
+/// The directory with theme files, like `~/.config/sqc/themes` on Linux.
+fn themes_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "sqc", "sqc").map(|dirs| dirs.config_dir().join("themes"))
+}
+
+/// Check if colors should be turned off, see https://no-color.org.
+pub fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some()
+}
+
+fn supports_true_color() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit")
+}
+
+/// Replace a true color with the closest color of the 256 color palette.
+fn to_256_color(color: Color) -> Color {
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+    // The palette has a 6x6x6 color cube from 16 and 24 shades of grey from 232.
+    let cube_index = |c: u8| match c {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (u16::from(c) - 35) / 40,
+    };
+    let cube_level = |i: u16| if i == 0 { 0 } else { i * 40 + 55 };
+    let (ri, gi, bi) = (cube_index(r), cube_index(g), cube_index(b));
+    let cube = (cube_level(ri), cube_level(gi), cube_level(bi));
+
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let grey_index = (average.saturating_sub(3) / 10).min(23);
+    let grey_level = grey_index * 10 + 8;
+
+    let distance = |(cr, cg, cb): (u16, u16, u16)| {
+        let d = |a: u8, b: u16| (i32::from(a) - i32::from(b)).pow(2);
+        d(r, cr) + d(g, cg) + d(b, cb)
+    };
+    if distance((grey_level, grey_level, grey_level)) < distance(cube) {
+        Color::Ansi256(232 + grey_index as u8)
+    } else {
+        Color::Ansi256(16 + (36 * ri + 6 * gi + bi) as u8)
+    }
+}
+
+/// A theme file, like:
+///
+/// ```toml
+/// base = "light"
+/// keyword = { fg = "#005fd7", bold = true }
+/// null = { fg = 245, italic = true }
+/// ```
+#[derive(Deserialize)]
+struct ThemeFile {
+    base: Option<String>,
+    #[serde(flatten)]
+    styles: BTreeMap<String, StyleConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleConfig {
+    fg: Option<ColorConfig>,
+    bg: Option<ColorConfig>,
+    #[serde(default)]
+    bold: bool,
+    #[serde(default)]
+    italic: bool,
+    #[serde(default)]
+    underline: bool,
+}
+
+/// A color in the 256 color palette, or a name like `red`, `#ff8700` or `255,135,0`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorConfig {
+    Index(u8),
+    Name(String),
+}
+
+impl ColorConfig {
+    fn to_color(&self) -> anyhow::Result<Color> {
+        match self {
+            ColorConfig::Index(index) => Ok(Color::Ansi256(*index)),
+            ColorConfig::Name(name) => match name.strip_prefix('#') {
+                Some(hex) if hex.len() == 6 => {
+                    let rgb = u32::from_str_radix(hex, 16)
+                        .with_context(|| format!("invalid color `{name}`"))?;
+                    Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+                }
+                _ => Ok(Color::from_str(name)?),
+            },
+        }
+    }
+}
+
+impl StyleConfig {
+    fn to_color_spec(&self) -> anyhow::Result<ColorSpec> {
+        let mut spec = ColorSpec::new();
+        spec.set_fg(self.fg.as_ref().map(ColorConfig::to_color).transpose()?)
+            .set_bg(self.bg.as_ref().map(ColorConfig::to_color).transpose()?)
+            .set_bold(self.bold)
+            .set_italic(self.italic)
+            .set_underline(self.underline);
+        Ok(spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_theme() {
+        let theme = Theme::parse(
+            "custom",
+            r##"
+            base = "light"
+            keyword = { fg = "#ff8700", bold = true }
+            null = { fg = 240, italic = true }
+            string = { fg = "red", bg = "0,0,95" }
+            "##,
+        )
+        .unwrap();
+        assert_eq!(theme.name, "custom");
+        assert_eq!(theme.keyword.fg(), Some(&Color::Rgb(255, 135, 0)));
+        assert!(theme.keyword.bold());
+        assert_eq!(theme.null.fg(), Some(&Color::Ansi256(240)));
+        assert!(theme.null.italic());
+        assert_eq!(theme.string.fg(), Some(&Color::Red));
+        assert_eq!(theme.string.bg(), Some(&Color::Rgb(0, 0, 95)));
+        // Not in the file, so from the base theme.
+        assert_eq!(theme.comment, Theme::light().comment);
+
+        assert!(Theme::parse("custom", "keyword = { fg = \"blurple\" }").is_err());
+        assert!(Theme::parse("custom", "keywords = { fg = 1 }").is_err());
+        assert!(Theme::parse("custom", "base = \"solarized\"").is_err());
+    }
+
+    #[test]
+    fn downgrade_true_colors() {
+        assert_eq!(to_256_color(Color::Rgb(0, 0, 0)), Color::Ansi256(16));
+        assert_eq!(to_256_color(Color::Rgb(255, 135, 0)), Color::Ansi256(208));
+        assert_eq!(to_256_color(Color::Rgb(128, 128, 128)), Color::Ansi256(244));
+        assert_eq!(to_256_color(Color::Red), Color::Red);
+    }
+}